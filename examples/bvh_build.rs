@@ -0,0 +1,98 @@
+use std::env;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use raytracing::bvh::{BVH, PartitionBy, SAHBucketStrategy, SAHLeafPolicy};
+use raytracing::material::Material;
+use raytracing::mesh::{MeshTriangle, TriangleMesh};
+use raytracing::vector::Vector3;
+
+/// Usage: `bvh_build [triangles]`
+///
+/// Times building a BVH over a rippled grid mesh of about `triangles` triangles (a million
+/// by default), once on a single thread and once on rayon's full thread pool, for each
+/// partitioning strategy. Nothing is rendered.
+fn main() -> Result<(), Box<dyn Error>> {
+    let triangles = match env::args().nth(1) {
+        Some(triangles) => triangles.parse()?,
+        None => 1_000_000,
+    };
+    let triangles = grid(triangles);
+    println!(
+        "{} triangles, {} threads",
+        triangles.len(),
+        rayon::current_num_threads()
+    );
+
+    let single_thread = rayon::ThreadPoolBuilder::new().num_threads(1).build()?;
+
+    for (name, partition_by) in [
+        ("longest axis bisect", PartitionBy::LongestAxisBisectSlice),
+        ("longest axis midpoint", PartitionBy::LongestAxisMidpoint),
+        (
+            "SAH, 16 buckets",
+            PartitionBy::SurfaceAreaHeuristic(
+                SAHBucketStrategy::EqualSize(16),
+                SAHLeafPolicy::default(),
+            ),
+        ),
+        (
+            "SAH, per surface",
+            PartitionBy::SurfaceAreaHeuristic(
+                SAHBucketStrategy::PerSurface,
+                SAHLeafPolicy::default(),
+            ),
+        ),
+    ] {
+        let sequential = single_thread.install(|| time_build(&triangles, &partition_by));
+        let parallel = time_build(&triangles, &partition_by);
+
+        println!(
+            "{name}: {sequential:#?} on one thread, {parallel:#?} in parallel ({:.1}x)",
+            sequential.as_secs_f64() / parallel.as_secs_f64()
+        );
+    }
+
+    Ok(())
+}
+
+fn time_build(triangles: &[MeshTriangle], partition_by: &PartitionBy) -> Duration {
+    let triangles = Box::from(triangles);
+
+    let start = Instant::now();
+    let bvh = BVH::from_slice(triangles, partition_by);
+    let elapsed = start.elapsed();
+
+    // don't count freeing it
+    drop(bvh);
+    elapsed
+}
+
+/// A square grid of at least `triangles` triangles, two per cell, rippled so that it isn't
+/// flat.
+fn grid(triangles: usize) -> Box<[MeshTriangle]> {
+    let cells = ((triangles as f64 / 2.0).sqrt().ceil() as u32).max(1);
+    let vertices = cells + 1;
+
+    let positions = (0..vertices * vertices)
+        .map(|i| {
+            let x = f64::from(i % vertices) / f64::from(cells);
+            let z = f64::from(i / vertices) / f64::from(cells);
+            Vector3::new(x, 0.05 * (20.0 * x).sin() * (20.0 * z).cos(), z)
+        })
+        .collect();
+    let indices = (0..cells * cells)
+        .flat_map(|cell| {
+            let corner = cell / cells * vertices + cell % cells;
+            let [a, b, c, d] = [corner, corner + 1, corner + vertices, corner + vertices + 1];
+            [[a, c, b], [b, c, d]]
+        })
+        .collect();
+    let mesh = TriangleMesh::new(positions, indices, None, None).unwrap();
+
+    let material = Material::Lambertian {
+        albedo: Vector3::new(0.5, 0.5, 0.5).into(),
+    };
+    MeshTriangle::all(Arc::new(mesh), Arc::new(material)).collect()
+}
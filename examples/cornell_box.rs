@@ -1,16 +1,33 @@
+use std::env;
 use std::error::Error;
+use std::path::PathBuf;
 
 use raytracing::camera::Camera;
 use raytracing::geometry::Geometry;
 use raytracing::material::Material;
 use raytracing::runner::RenderRunner;
 use raytracing::surface::Surface;
-use raytracing::vector::Vector3;
+use raytracing::texture::{Texture, WrapMode};
+use raytracing::vector::{Transform, Vector3};
 
+/// Usage: `cornell_box [output] [back wall image]`
 fn main() -> Result<(), Box<dyn Error>> {
-    let world = scene();
+    let world = match env::args_os().nth(2) {
+        Some(path) => scene_with_back_wall(Texture::open(path, WrapMode::Clamp)?),
+        None => scene(),
+    };
 
-    let camera = Camera {
+    RenderRunner {
+        camera: camera(),
+        ..Default::default()
+    }
+    .run_cli(env::args_os().nth(1).map(PathBuf::from), |runner| {
+        runner.run(world)
+    })
+}
+
+pub fn camera() -> Camera {
+    Camera {
         aspect_ratio: 1.0,
         image_width: 600,
         samples_per_pixel: 2000,
@@ -23,19 +40,19 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         background: Vector3::new(0.0, 0.0, 0.0),
 
-        ..Default::default()
-    };
-
-    RenderRunner {
-        camera,
         ..Default::default()
     }
-    .run(world)
 }
 
-fn scene() -> Box<[Surface]> {
+pub fn scene() -> Box<[Surface]> {
+    scene_with_back_wall(Vector3::new(0.73, 0.73, 0.73).into())
+}
+
+/// The same scene, with `back_wall` mapped onto the back wall so it reads the right way
+/// round from the camera.
+pub fn scene_with_back_wall(back_wall: Texture) -> Box<[Surface]> {
     let white = Material::Lambertian {
-        albedo: Vector3::new(0.73, 0.73, 0.73),
+        albedo: Vector3::new(0.73, 0.73, 0.73).into(),
     };
 
     let mut surfaces = Vec::new();
@@ -45,7 +62,7 @@ fn scene() -> Box<[Surface]> {
         Vector3::new(0.0, 0.0, 0.0) + Vector3::new(265.0, 0.0, 295.0),
         Vector3::new(165.0, 330.0, 165.0) + Vector3::new(265.0, 0.0, 295.0),
         Material::Metal {
-            albedo: Vector3::new(0.7, 0.6, 0.5),
+            albedo: Vector3::new(0.7, 0.6, 0.5).into(),
             fuzz_radius: 0.0,
         },
         18.0_f64.to_radians(),
@@ -59,7 +76,7 @@ fn scene() -> Box<[Surface]> {
         (-18.0_f64).to_radians(),
     ));
 
-    surfaces.extend(cornell_box());
+    surfaces.extend(cornell_box(back_wall));
 
     surfaces.into_boxed_slice()
 }
@@ -68,22 +85,12 @@ fn box_geometry(a: Vector3, b: Vector3, material: Material, theta: f64) -> Box<[
     let min = Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
     let max = Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
 
-    // Calculate center for rotation
+    // Rotation around the Y-axis, through the box's center
     let center = min + (max - min) * 0.5;
-
-    // Rotation around Y-axis
-    let cos_theta = theta.cos();
-    let sin_theta = theta.sin();
-
-    let rotate_y = |v: Vector3| -> Vector3 {
-        let relative = v - center;
-        let rotated = Vector3::new(
-            cos_theta * relative.x + sin_theta * relative.z,
-            relative.y,
-            -sin_theta * relative.x + cos_theta * relative.z,
-        );
-        rotated + center
-    };
+    let rotation = Transform::translation(-center)
+        .then(Transform::rotation(Vector3::new(0.0, 1.0, 0.0), theta).unwrap())
+        .then(Transform::translation(center));
+    let rotate_y = |v: Vector3| rotation.point(v);
 
     // Define all 8 vertices of the box
     let v000 = rotate_y(Vector3::new(min.x, min.y, min.z));
@@ -129,18 +136,18 @@ fn box_geometry(a: Vector3, b: Vector3, material: Material, theta: f64) -> Box<[
     ])
 }
 
-fn cornell_box() -> Box<[Surface]> {
+fn cornell_box(back_wall: Texture) -> Box<[Surface]> {
     let red = Material::Lambertian {
-        albedo: Vector3::new(0.65, 0.05, 0.05),
+        albedo: Vector3::new(0.65, 0.05, 0.05).into(),
     };
     let white = Material::Lambertian {
-        albedo: Vector3::new(0.73, 0.73, 0.73),
+        albedo: Vector3::new(0.73, 0.73, 0.73).into(),
     };
     let green = Material::Lambertian {
-        albedo: Vector3::new(0.12, 0.45, 0.15),
+        albedo: Vector3::new(0.12, 0.45, 0.15).into(),
     };
     let light = Material::DiffuseLight {
-        emit: Vector3::new(50.0, 50.0, 50.0),
+        emit: Vector3::new(50.0, 50.0, 50.0).into(),
     };
 
     Box::from([
@@ -189,14 +196,15 @@ fn cornell_box() -> Box<[Surface]> {
             ),
             white.clone(),
         ),
-        // Back wall
+        // Back wall, with u running right to left in world space, which is left to right
+        // as the camera sees it
         Surface::new(
             Geometry::quadrilateral(
-                Vector3::new(0.0, 0.0, 555.0),
-                Vector3::new(555.0, 0.0, 0.0),
+                Vector3::new(555.0, 0.0, 555.0),
+                Vector3::new(-555.0, 0.0, 0.0),
                 Vector3::new(0.0, 555.0, 0.0),
             ),
-            white.clone(),
+            Material::Lambertian { albedo: back_wall },
         ),
     ])
 }
@@ -1,6 +1,9 @@
+use std::env;
 use std::error::Error;
+use std::path::PathBuf;
 
-use rand::{random, random_range};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use raytracing::camera::Camera;
 use raytracing::geometry::{ConstructSphereError, Geometry};
 use raytracing::interval::Interval;
@@ -34,7 +37,9 @@ fn main() -> Result<(), Box<dyn Error>> {
         camera,
         ..Default::default()
     }
-    .run(world)
+    .run_cli(env::args_os().nth(1).map(PathBuf::from), |runner| {
+        runner.run(world)
+    })
 }
 
 fn cover_spheres() -> Result<Box<[Surface]>, ConstructSphereError> {
@@ -42,8 +47,11 @@ fn cover_spheres() -> Result<Box<[Surface]>, ConstructSphereError> {
     const BIG_SPHERES_RADIUS: f64 = 1.0;
 
     let ground_material = Material::Lambertian {
-        albedo: Vector3::new(0.5, 0.5, 0.5),
+        albedo: Vector3::new(0.5, 0.5, 0.5).into(),
     };
+    // the sphere layout is random too, so keep it reproducible
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+
     let mut world: Vec<Surface> = vec![Surface::new(
         Geometry::sphere(Vector3::new(0.0, -1000.0, 0.0), 1000.0)?,
         ground_material,
@@ -53,7 +61,7 @@ fn cover_spheres() -> Result<Box<[Surface]>, ConstructSphereError> {
         let back_sphere = Surface::new(
             Geometry::sphere(Vector3::new(-4.0, 1.0, 0.0), BIG_SPHERES_RADIUS)?,
             Material::Lambertian {
-                albedo: Vector3::new(0.4, 0.2, 0.1),
+                albedo: Vector3::new(0.4, 0.2, 0.1).into(),
             },
         );
 
@@ -67,7 +75,7 @@ fn cover_spheres() -> Result<Box<[Surface]>, ConstructSphereError> {
         let front_sphere = Surface::new(
             Geometry::sphere(Vector3::new(4.0, 1.0, 0.0), BIG_SPHERES_RADIUS)?,
             Material::Metal {
-                albedo: Vector3::new(0.7, 0.6, 0.5),
+                albedo: Vector3::new(0.7, 0.6, 0.5).into(),
                 fuzz_radius: 0.0,
             },
         );
@@ -78,9 +86,9 @@ fn cover_spheres() -> Result<Box<[Surface]>, ConstructSphereError> {
     for a in -11..11 {
         for b in -11..11 {
             let center = Vector3::new(
-                a as f64 + 0.9 * random::<f64>(),
+                a as f64 + 0.9 * rng.random::<f64>(),
                 SMALL_SPHERES_RADIUS,
-                b as f64 + 0.9 * random::<f64>(),
+                b as f64 + 0.9 * rng.random::<f64>(),
             );
 
             if big_spheres
@@ -100,16 +108,16 @@ fn cover_spheres() -> Result<Box<[Surface]>, ConstructSphereError> {
             }
 
             let material = {
-                let choose_material = random::<f64>();
+                let choose_material = rng.random::<f64>();
 
                 if choose_material < 0.8 {
                     Material::Lambertian {
-                        albedo: Vector3::random() * Vector3::random(),
+                        albedo: (Vector3::random(&mut rng) * Vector3::random(&mut rng)).into(),
                     }
                 } else if choose_material < 0.95 {
                     Material::Metal {
-                        albedo: Vector3::random_range(Interval::new(0.5, 1.0)),
-                        fuzz_radius: random_range(0.0..0.5),
+                        albedo: Vector3::random_range(Interval::new(0.5, 1.0), &mut rng).into(),
+                        fuzz_radius: rng.random_range(0.0..0.5),
                     }
                 } else {
                     Material::Dielectric {
@@ -1,4 +1,6 @@
+use std::env;
 use std::error::Error;
+use std::path::PathBuf;
 
 use raytracing::camera::Camera;
 use raytracing::geometry::{ConstructSphereError, Geometry};
@@ -10,7 +12,17 @@ use raytracing::vector::Vector3;
 fn main() -> Result<(), Box<dyn Error>> {
     let world = demo_spheres()?;
 
-    let camera = Camera {
+    RenderRunner {
+        camera: camera(),
+        ..Default::default()
+    }
+    .run_cli(env::args_os().nth(1).map(PathBuf::from), |runner| {
+        runner.run(world)
+    })
+}
+
+pub fn camera() -> Camera {
+    Camera {
         aspect_ratio: 16.0 / 9.0,
         image_width: 400,
         samples_per_pixel: 100,
@@ -25,22 +37,16 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         background: Vector3::new(0.7, 0.8, 1.0),
 
-        ..Default::default()
-    };
-
-    RenderRunner {
-        camera,
         ..Default::default()
     }
-    .run(world)
 }
 
-fn demo_spheres() -> Result<Box<[Surface]>, ConstructSphereError> {
+pub fn demo_spheres() -> Result<Box<[Surface]>, ConstructSphereError> {
     let material_ground = Material::Lambertian {
-        albedo: Vector3::new(0.8, 0.8, 0.0),
+        albedo: Vector3::new(0.8, 0.8, 0.0).into(),
     };
     let material_center = Material::Lambertian {
-        albedo: Vector3::new(0.1, 0.2, 0.5),
+        albedo: Vector3::new(0.1, 0.2, 0.5).into(),
     };
     let material_left = Material::Dielectric {
         refraction_index: 1.5,
@@ -49,7 +55,7 @@ fn demo_spheres() -> Result<Box<[Surface]>, ConstructSphereError> {
         refraction_index: 1.0 / 1.5,
     };
     let material_right = Material::Metal {
-        albedo: Vector3::new(0.8, 0.6, 0.2),
+        albedo: Vector3::new(0.8, 0.6, 0.2).into(),
         fuzz_radius: 1.0,
     };
 
@@ -1,4 +1,6 @@
+use std::env;
 use std::error::Error;
+use std::path::PathBuf;
 
 use raytracing::camera::Camera;
 use raytracing::geometry::Geometry;
@@ -10,7 +12,17 @@ use raytracing::vector::Vector3;
 fn main() -> Result<(), Box<dyn Error>> {
     let world = hello_triangle();
 
-    let camera = Camera {
+    RenderRunner {
+        camera: camera(),
+        ..Default::default()
+    }
+    .run_cli(env::args_os().nth(1).map(PathBuf::from), |runner| {
+        runner.run(world)
+    })
+}
+
+pub fn camera() -> Camera {
+    Camera {
         aspect_ratio: 16.0 / 9.0,
         image_width: 1920,
         samples_per_pixel: 1,
@@ -21,17 +33,11 @@ fn main() -> Result<(), Box<dyn Error>> {
         look_at: Vector3::new(0.0, 0.0, 0.0),
         v_up: Vector3::new(0.0, 1.0, 0.0),
 
-        ..Default::default()
-    };
-
-    RenderRunner {
-        camera,
         ..Default::default()
     }
-    .run(world)
 }
 
-fn hello_triangle() -> Box<[Surface]> {
+pub fn hello_triangle() -> Box<[Surface]> {
     let uv_gradient = Material::UVGradient { intensity: 1.0 };
 
     let side_length = 2.0;
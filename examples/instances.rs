@@ -0,0 +1,133 @@
+use std::env;
+use std::error::Error;
+use std::f64::consts::TAU;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use raytracing::bvh::{BVH, PartitionBy, SAHBucketStrategy, SAHLeafPolicy};
+use raytracing::camera::Camera;
+use raytracing::geometry::Geometry;
+use raytracing::instance::Instance;
+use raytracing::material::Material;
+use raytracing::mesh::{MeshTriangle, TriangleMesh};
+use raytracing::runner::RenderRunner;
+use raytracing::surface::{Hittable, Surface};
+use raytracing::vector::{Transform, Vector3};
+
+/// Usage: `instances [output]`
+///
+/// Scatters copies of one smooth-shaded octahedron across a floor. Every copy shares the
+/// same mesh and BVH, and only differs by its transform.
+fn main() -> Result<(), Box<dyn Error>> {
+    RenderRunner {
+        camera: camera(),
+        ..Default::default()
+    }
+    .run_cli(env::args_os().nth(1).map(PathBuf::from), |runner| {
+        runner.run_with_instances(floor(), instances(40))
+    })
+}
+
+pub fn camera() -> Camera {
+    Camera {
+        aspect_ratio: 16.0 / 9.0,
+        image_width: 800,
+        samples_per_pixel: 128,
+        max_depth: 50,
+
+        v_fov: 35.0,
+        look_from: Vector3::new(0.0, 9.0, 22.0),
+        look_at: Vector3::new(0.0, 0.0, 0.0),
+        v_up: Vector3::new(0.0, 1.0, 0.0),
+
+        background: Vector3::new(0.7, 0.8, 1.0),
+
+        ..Default::default()
+    }
+}
+
+pub fn floor() -> Box<[Surface]> {
+    Box::from([Surface::new(
+        Geometry::quadrilateral(
+            Vector3::new(-100.0, 0.0, 100.0),
+            Vector3::new(200.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -200.0),
+        ),
+        Material::Lambertian {
+            albedo: Vector3::new(0.5, 0.5, 0.5).into(),
+        },
+    )])
+}
+
+/// A `side` by `side` grid of octahedra, each randomly turned, tilted and sized.
+pub fn instances(side: u32) -> Box<[Instance<dyn Hittable>]> {
+    let material = Material::Lambertian {
+        albedo: Vector3::new(0.8, 0.3, 0.2).into(),
+    };
+    let object: Arc<dyn Hittable> = Arc::new(BVH::from_slice(
+        MeshTriangle::all(Arc::new(octahedron()), Arc::new(material)).collect(),
+        &PartitionBy::SurfaceAreaHeuristic(SAHBucketStrategy::PerSurface, SAHLeafPolicy::default()),
+    ));
+
+    // keep the layout reproducible
+    let mut rng = ChaCha8Rng::seed_from_u64(0x1257);
+    let spacing = 2.0;
+    let offset = spacing * f64::from(side - 1) / 2.0;
+
+    (0..side * side)
+        .map(|i| {
+            let scale = rng.random_range(0.4..0.9);
+            let position = Vector3::new(
+                spacing * f64::from(i % side) - offset,
+                scale,
+                spacing * f64::from(i / side) - offset,
+            );
+            let transform = Transform::scaling(Vector3::new(scale, scale, scale))
+                .unwrap()
+                .then(
+                    Transform::rotation(Vector3::new(1.0, 0.0, 0.0), rng.random_range(-0.3..0.3))
+                        .unwrap(),
+                )
+                .then(
+                    Transform::rotation(Vector3::new(0.0, 1.0, 0.0), rng.random_range(0.0..TAU))
+                        .unwrap(),
+                )
+                .then(Transform::translation(position));
+
+            Instance::new(Arc::clone(&object), transform)
+        })
+        .collect()
+}
+
+/// A unit octahedron, with normals pointing straight out from its center so it shades
+/// like a rounded gem.
+fn octahedron() -> TriangleMesh {
+    let positions = [
+        Vector3::new(1.0, 0.0, 0.0),
+        Vector3::new(-1.0, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        Vector3::new(0.0, -1.0, 0.0),
+        Vector3::new(0.0, 0.0, 1.0),
+        Vector3::new(0.0, 0.0, -1.0),
+    ];
+    let indices = [
+        [0, 2, 4],
+        [4, 2, 1],
+        [1, 2, 5],
+        [5, 2, 0],
+        [4, 3, 0],
+        [1, 3, 4],
+        [5, 3, 1],
+        [0, 3, 5],
+    ];
+
+    TriangleMesh::new(
+        positions.into(),
+        indices.into(),
+        Some(positions.into()),
+        None,
+    )
+    .unwrap()
+}
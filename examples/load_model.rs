@@ -0,0 +1,103 @@
+use std::env;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use raytracing::aabb::AABB;
+use raytracing::bvh::BVH;
+use raytracing::camera::Camera;
+use raytracing::geometry::Geometry;
+use raytracing::instance::Instance;
+use raytracing::loader::{LoadModelError, obj, ply};
+use raytracing::material::Material;
+use raytracing::mesh::MeshTriangle;
+use raytracing::runner::RenderRunner;
+use raytracing::surface::{Hittable, Surface};
+use raytracing::vector::{Transform, Vector3};
+
+/// Usage: `load_model [output] [model]`
+///
+/// Renders a Wavefront OBJ or Stanford PLY model standing on a gray floor under a sky,
+/// framed from the front-right. PLY models are colored by their vertex colors if they have
+/// any, and plain gray otherwise. Without a model, renders the small OBJ test fixture.
+fn main() -> Result<(), Box<dyn Error>> {
+    let model = env::args_os().nth(2).map_or_else(
+        || PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/obj/materials.obj"),
+        PathBuf::from,
+    );
+    let triangles = load(&model)?;
+    let bounds = triangles.as_ref().bounding_box();
+
+    RenderRunner {
+        camera: camera(&bounds),
+        ..Default::default()
+    }
+    .run_cli(env::args_os().nth(1).map(PathBuf::from), |runner| {
+        // the model gets a tree of its own, over triangles that share its mesh and materials
+        let model: Arc<dyn Hittable> =
+            Arc::new(BVH::from_slice(triangles, &runner.partition_strategy));
+        runner.run_with_instances(
+            floor(&bounds),
+            Box::new([Instance::new(model, Transform::IDENTITY)]),
+        )
+    })
+}
+
+fn load(path: &Path) -> Result<Box<[MeshTriangle]>, LoadModelError> {
+    let is_ply = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ply"));
+    if !is_ply {
+        return Ok(obj::load(path)?.triangles().collect());
+    }
+
+    let ply = ply::load(path)?;
+    let triangles = match ply.colored_triangles() {
+        Some(triangles) => triangles.collect(),
+        None => ply
+            .triangles(Material::Lambertian {
+                albedo: Vector3::new(0.7, 0.7, 0.7).into(),
+            })
+            .collect(),
+    };
+    Ok(triangles)
+}
+
+pub fn camera(bounds: &AABB) -> Camera {
+    let radius = bounds.dimensions().length() / 2.0;
+    let v_fov: f64 = 30.0;
+    // far enough back for the model's bounding sphere to fit the frame vertically
+    let distance = radius / (v_fov.to_radians() / 2.0).sin();
+
+    Camera {
+        aspect_ratio: 16.0 / 9.0,
+        image_width: 800,
+        samples_per_pixel: 256,
+        max_depth: 50,
+
+        v_fov,
+        look_from: bounds.centroid() + distance * Vector3::new(0.6, 0.4, 1.0).to_unit(),
+        look_at: bounds.centroid(),
+        v_up: Vector3::new(0.0, 1.0, 0.0),
+
+        background: Vector3::new(0.7, 0.8, 1.0),
+
+        ..Default::default()
+    }
+}
+
+/// A gray floor under the model, reaching far out on every side.
+pub fn floor(bounds: &AABB) -> Box<[Surface]> {
+    let extent = 10.0 * bounds.dimensions().max_component();
+    let center = bounds.centroid();
+    Box::new([Surface::new(
+        Geometry::quadrilateral(
+            Vector3::new(center.x - extent, bounds.min().y, center.z + extent),
+            Vector3::new(2.0 * extent, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -2.0 * extent),
+        ),
+        Material::Lambertian {
+            albedo: Vector3::new(0.5, 0.5, 0.5).into(),
+        },
+    )])
+}
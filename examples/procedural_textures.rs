@@ -0,0 +1,100 @@
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use raytracing::camera::Camera;
+use raytracing::geometry::{ConstructSphereError, Geometry};
+use raytracing::material::Material;
+use raytracing::runner::RenderRunner;
+use raytracing::surface::Surface;
+use raytracing::texture::{Perlin, Texture};
+use raytracing::vector::Vector3;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let world = procedural_textures()?;
+
+    RenderRunner {
+        camera: camera(),
+        ..Default::default()
+    }
+    .run_cli(env::args_os().nth(1).map(PathBuf::from), |runner| {
+        runner.run(world)
+    })
+}
+
+pub fn camera() -> Camera {
+    Camera {
+        aspect_ratio: 16.0 / 9.0,
+        image_width: 400,
+        samples_per_pixel: 100,
+        max_depth: 50,
+
+        look_from: Vector3::new(0.0, 3.0, 9.0),
+        look_at: Vector3::new(0.0, 0.5, 0.0),
+        v_fov: 30.0,
+
+        defocus_angle: 0.0,
+        focus_dist: 1.0,
+
+        background: Vector3::new(0.7, 0.8, 1.0),
+
+        ..Default::default()
+    }
+}
+
+pub fn procedural_textures() -> Result<Box<[Surface]>, ConstructSphereError> {
+    let perlin = Arc::new(Perlin::new(0));
+    let lambertian = |texture: Texture| Material::Lambertian { albedo: texture };
+
+    let ground = Texture::Checker {
+        scale: 1.0,
+        even: Box::new(Vector3::new(0.2, 0.3, 0.1).into()),
+        odd: Box::new(Vector3::new(0.9, 0.9, 0.9).into()),
+    };
+    let beach_ball = Texture::UVChecker {
+        columns: 8,
+        rows: 4,
+        even: Box::new(Vector3::new(0.8, 0.1, 0.1).into()),
+        odd: Box::new(Vector3::new(0.9, 0.9, 0.8).into()),
+    };
+    let marble = Texture::Marble {
+        perlin: perlin.clone(),
+        scale: 4.0,
+        octaves: 7,
+    };
+    let wood = Texture::Wood {
+        perlin: perlin.clone(),
+        scale: 6.0,
+        light: Vector3::new(0.75, 0.5, 0.3),
+        dark: Vector3::new(0.35, 0.2, 0.1),
+    };
+    let turbulence = Texture::Turbulence {
+        perlin,
+        scale: 3.0,
+        octaves: 7,
+    };
+
+    Ok(Box::from([
+        Surface::new(
+            Geometry::sphere(Vector3::new(0.0, -1000.0, 0.0), 1000.0)?,
+            lambertian(ground),
+        ),
+        Surface::new(
+            Geometry::sphere(Vector3::new(-3.0, 1.0, 0.0), 1.0)?,
+            lambertian(beach_ball),
+        ),
+        Surface::new(
+            Geometry::sphere(Vector3::new(-1.0, 1.0, -1.0), 1.0)?,
+            lambertian(marble),
+        ),
+        Surface::new(
+            Geometry::sphere(Vector3::new(1.0, 1.0, -1.0), 1.0)?,
+            lambertian(wood),
+        ),
+        Surface::new(
+            Geometry::sphere(Vector3::new(3.0, 1.0, 0.0), 1.0)?,
+            lambertian(turbulence),
+        ),
+    ]))
+}
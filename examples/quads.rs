@@ -1,4 +1,6 @@
+use std::env;
 use std::error::Error;
+use std::path::PathBuf;
 
 use raytracing::camera::Camera;
 use raytracing::geometry::Geometry;
@@ -10,7 +12,17 @@ use raytracing::vector::Vector3;
 fn main() -> Result<(), Box<dyn Error>> {
     let world = quads();
 
-    let camera = Camera {
+    RenderRunner {
+        camera: camera(),
+        ..Default::default()
+    }
+    .run_cli(env::args_os().nth(1).map(PathBuf::from), |runner| {
+        runner.run(world)
+    })
+}
+
+pub fn camera() -> Camera {
+    Camera {
         aspect_ratio: 1.0,
         image_width: 400,
         samples_per_pixel: 100,
@@ -25,31 +37,25 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         background: Vector3::new(0.7, 0.8, 1.0),
 
-        ..Default::default()
-    };
-
-    RenderRunner {
-        camera,
         ..Default::default()
     }
-    .run(world)
 }
 
-fn quads() -> Box<[Surface]> {
+pub fn quads() -> Box<[Surface]> {
     let left_red = Material::Lambertian {
-        albedo: Vector3::new(1.0, 0.2, 0.2),
+        albedo: Vector3::new(1.0, 0.2, 0.2).into(),
     };
     let back_green = Material::Lambertian {
-        albedo: Vector3::new(0.2, 1.0, 0.2),
+        albedo: Vector3::new(0.2, 1.0, 0.2).into(),
     };
     let right_blue = Material::Lambertian {
-        albedo: Vector3::new(0.2, 0.2, 1.0),
+        albedo: Vector3::new(0.2, 0.2, 1.0).into(),
     };
     let upper_orange = Material::Lambertian {
-        albedo: Vector3::new(1.0, 0.5, 0.0),
+        albedo: Vector3::new(1.0, 0.5, 0.0).into(),
     };
     let lower_teal = Material::Lambertian {
-        albedo: Vector3::new(0.2, 0.8, 0.8),
+        albedo: Vector3::new(0.2, 0.8, 0.8).into(),
     };
 
     // Quads
@@ -1,4 +1,6 @@
+use std::env;
 use std::error::Error;
+use std::path::PathBuf;
 
 use raytracing::camera::Camera;
 use raytracing::geometry::{ConstructSphereError, Geometry};
@@ -10,7 +12,17 @@ use raytracing::vector::Vector3;
 fn main() -> Result<(), Box<dyn Error>> {
     let world = simple_light()?;
 
-    let camera = Camera {
+    RenderRunner {
+        camera: camera(),
+        ..Default::default()
+    }
+    .run_cli(env::args_os().nth(1).map(PathBuf::from), |runner| {
+        runner.run(world)
+    })
+}
+
+pub fn camera() -> Camera {
+    Camera {
         aspect_ratio: 16.0 / 9.0,
         image_width: 400,
         samples_per_pixel: 1000,
@@ -24,29 +36,23 @@ fn main() -> Result<(), Box<dyn Error>> {
         defocus_angle: 0.0,
         focus_dist: 1.0,
 
-        ..Default::default()
-    };
-
-    RenderRunner {
-        camera,
         ..Default::default()
     }
-    .run(world)
 }
 
-fn simple_light() -> Result<Box<[Surface]>, ConstructSphereError> {
+pub fn simple_light() -> Result<Box<[Surface]>, ConstructSphereError> {
     // Ground sphere with warm beige color
     let ground_material = Material::Lambertian {
-        albedo: Vector3::new(0.6, 0.5, 0.4),
+        albedo: Vector3::new(0.6, 0.5, 0.4).into(),
     };
 
     // Small sphere with soft pink color
     let sphere_material = Material::Lambertian {
-        albedo: Vector3::new(0.8, 0.4, 0.6),
+        albedo: Vector3::new(0.8, 0.4, 0.6).into(),
     };
 
     let light_material = Material::DiffuseLight {
-        emit: Vector3::new(10.0, 10.0, 10.0),
+        emit: Vector3::new(10.0, 10.0, 10.0).into(),
     };
 
     Ok(Box::from([
@@ -1,4 +1,6 @@
+use std::env;
 use std::error::Error;
+use std::path::PathBuf;
 
 use raytracing::camera::Camera;
 use raytracing::geometry::{ConstructSphereError, Geometry};
@@ -10,7 +12,17 @@ use raytracing::vector::Vector3;
 fn main() -> Result<(), Box<dyn Error>> {
     let world = simple_scene()?;
 
-    let camera = Camera {
+    RenderRunner {
+        camera: camera(),
+        ..Default::default()
+    }
+    .run_cli(env::args_os().nth(1).map(PathBuf::from), |runner| {
+        runner.run(world)
+    })
+}
+
+pub fn camera() -> Camera {
+    Camera {
         aspect_ratio: 16.0 / 9.0,
         image_width: 400,
         samples_per_pixel: 100,
@@ -25,25 +37,19 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         background: Vector3::new(0.7, 0.8, 1.0),
 
-        ..Default::default()
-    };
-
-    RenderRunner {
-        camera,
         ..Default::default()
     }
-    .run(world)
 }
 
-fn simple_scene() -> Result<Box<[Surface]>, ConstructSphereError> {
+pub fn simple_scene() -> Result<Box<[Surface]>, ConstructSphereError> {
     let red_material = Material::Lambertian {
-        albedo: Vector3::new(0.7, 0.3, 0.3),
+        albedo: Vector3::new(0.7, 0.3, 0.3).into(),
     };
     let blue_material = Material::Lambertian {
-        albedo: Vector3::new(0.3, 0.3, 0.7),
+        albedo: Vector3::new(0.3, 0.3, 0.7).into(),
     };
     let metal_material = Material::Metal {
-        albedo: Vector3::new(0.8, 0.8, 0.9),
+        albedo: Vector3::new(0.8, 0.8, 0.9).into(),
         fuzz_radius: 0.0,
     };
 
@@ -63,7 +69,7 @@ fn simple_scene() -> Result<Box<[Surface]>, ConstructSphereError> {
         Surface::new(
             Geometry::sphere(Vector3::new(0.0, -100.5, -1.0), 100.0)?,
             Material::Lambertian {
-                albedo: Vector3::new(0.8, 0.8, 0.0),
+                albedo: Vector3::new(0.8, 0.8, 0.0).into(),
             },
         ),
     ]))
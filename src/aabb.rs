@@ -1,6 +1,8 @@
-use std::f64;
-
-use crate::{interval::Interval, ray::Ray, vector::Vector3};
+use crate::{
+    interval::Interval,
+    ray::Ray,
+    vector::{Transform, Vector3},
+};
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct AABB {
@@ -75,14 +77,51 @@ impl AABB {
         lowers_max < uppers_min
     }
 
+    /// The smallest box enclosing this one after `transform`, which may be looser than a
+    /// box around the transformed contents.
+    pub fn transformed(&self, transform: &Transform) -> AABB {
+        if self.min.x > self.max.x {
+            // keep empty boxes empty instead of transforming their infinities
+            return self.clone();
+        }
+
+        (0..8)
+            .map(|corner| {
+                let pick = |bit: u32, min: f64, max: f64| if corner & bit == 0 { min } else { max };
+                transform.point(Vector3::new(
+                    pick(1, self.min.x, self.max.x),
+                    pick(2, self.min.y, self.max.y),
+                    pick(4, self.min.z, self.max.z),
+                ))
+            })
+            .map(|p| AABB::new(p, p))
+            .fold(AABB::EMPTY, AABB::merge)
+    }
+
+    /// Thickens any dimension thinner than `padding` by `padding` on either side, so flat
+    /// shapes still enclose some volume. Other dimensions are left alone.
     pub fn padded(&self, padding: f64) -> AABB {
         let dims = self.dimensions();
-        let padding = Vector3::new(
-            dims.x.max(padding),
-            dims.y.max(padding),
-            dims.z.max(padding),
-        );
+        let pad = |extent: f64| if extent < padding { padding } else { 0.0 };
+        let padding = Vector3::new(pad(dims.x), pad(dims.y), pad(dims.z));
 
         AABB::new(self.min - padding, self.max + padding)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_padded_only_thickens_flat_dimensions() {
+        let flat = AABB::new(Vector3::new(0.0, -2.0, 1.0), Vector3::new(4.0, 2.0, 1.0));
+        assert_eq!(
+            flat.padded(0.25),
+            AABB::new(Vector3::new(0.0, -2.0, 0.75), Vector3::new(4.0, 2.0, 1.25))
+        );
+
+        let solid = AABB::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(solid.padded(0.25), solid);
+    }
+}
@@ -1,6 +1,9 @@
+use std::ops::Range;
+
 use crate::{
     aabb::AABB,
     geometry::Hit,
+    instance::Instance,
     interval::Interval,
     material::Material,
     ray::Ray,
@@ -9,9 +12,9 @@ use crate::{
 
 mod partition;
 
-/// Strategies for partitioning the surfaces in a given bounding volume.
+/// Strategies for partitioning the primitives in a given bounding volume.
 pub enum PartitionBy {
-    /// Sort surfaces by position along the longest axis and place half in each subtree.
+    /// Sort primitives by position along the longest axis and place half in each subtree.
     LongestAxisBisectSlice,
 
     /// Partition by position relative to midpoint of total bounding box's longest axis.
@@ -20,8 +23,10 @@ pub enum PartitionBy {
     /// At each volume split, choose the splitting plane that minimizes cost
     /// as defined by the surface area heuristic.
     ///
-    /// The bucketing strategy controls what candidate splitting planes are evaluated.
-    SurfaceAreaHeuristic(SAHBucketStrategy),
+    /// The bucketing strategy controls what candidate splitting planes are evaluated, and
+    /// the leaf policy when not splitting at all is cheaper. The other strategies split
+    /// all the way down to one primitive per leaf.
+    SurfaceAreaHeuristic(SAHBucketStrategy, SAHLeafPolicy),
 }
 
 /// Strategies for identifying candidate splitting planes
@@ -33,17 +38,59 @@ pub enum SAHBucketStrategy {
     PerSurface,
 }
 
+/// When the surface area heuristic should stop splitting and keep primitives together in
+/// one leaf.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SAHLeafPolicy {
+    /// Leaves never hold more primitives than this. 1 always splits down to single
+    /// primitives.
+    pub max_leaf_size: usize,
+
+    /// The cost of traversing an internal node, relative to testing a ray against one
+    /// primitive. Higher values favor fewer, fuller leaves.
+    pub traversal_cost: f64,
+}
+
+impl Default for SAHLeafPolicy {
+    fn default() -> Self {
+        Self {
+            max_leaf_size: 4,
+            traversal_cost: 1.0,
+        }
+    }
+}
+
 impl PartitionBy {
-    fn partition<'s>(&self, surfaces: &'s mut [Surface]) -> (&'s mut [Surface], &'s mut [Surface]) {
-        match self {
-            PartitionBy::LongestAxisBisectSlice => partition::longest_axis_bisect_slice(surfaces),
-            PartitionBy::LongestAxisMidpoint => partition::longest_axis_midpoint(surfaces),
-            PartitionBy::SurfaceAreaHeuristic(bucket_strategy) => match bucket_strategy {
-                SAHBucketStrategy::EqualSize(buckets) => {
-                    partition::sah::equal_size::partition(surfaces, *buckets)
+    /// Splits `surfaces` in two, or returns `None` to keep them in one leaf.
+    fn partition<'s, T: Hittable>(
+        &self,
+        surfaces: &'s mut [T],
+    ) -> Option<(&'s mut [T], &'s mut [T])> {
+        let n_left = match self {
+            PartitionBy::LongestAxisBisectSlice => {
+                Some(partition::longest_axis_bisect_slice(surfaces))
+            }
+            PartitionBy::LongestAxisMidpoint => Some(partition::longest_axis_midpoint(surfaces)),
+            PartitionBy::SurfaceAreaHeuristic(bucket_strategy, leaf_policy) => {
+                match bucket_strategy {
+                    SAHBucketStrategy::EqualSize(buckets) => {
+                        partition::sah::equal_size::partition(surfaces, *buckets, leaf_policy)
+                    }
+                    SAHBucketStrategy::PerSurface => {
+                        partition::sah::per_surface::partition(surfaces, leaf_policy)
+                    }
                 }
-                SAHBucketStrategy::PerSurface => partition::sah::per_surface::partition(surfaces),
-            },
+            }
+        }?
+        .0
+        .len();
+
+        // surfaces with coincident centroids (like duplicated mesh faces) can all land on
+        // one side, and then any split is as good as another
+        if n_left == 0 || n_left == surfaces.len() {
+            Some(surfaces.split_at_mut(surfaces.len() / 2))
+        } else {
+            Some(surfaces.split_at_mut(n_left))
         }
     }
 }
@@ -53,7 +100,8 @@ enum Node {
     Placeholder,
     /// right_idx, bounding_box
     Internal(Option<usize>, AABB),
-    Leaf(Surface),
+    /// range of primitives, bounding_box
+    Leaf(Range<usize>, AABB),
 }
 
 impl Node {
@@ -63,77 +111,57 @@ impl Node {
                 unreachable!("No code path should ever get the bounding box of a placeholder node")
             }
             Node::Internal(_, aabb) => aabb.clone(),
-            Node::Leaf(surface) => surface.bounding_box(),
+            Node::Leaf(_, aabb) => aabb.clone(),
         }
     }
 }
 
-pub struct BVH {
+/// A bounding volume hierarchy over any kind of [`Hittable`] primitive: surfaces by
+/// default, but also [`Instance`]s (see [`TopLevelBVH`]), other BVHs, or anything else
+/// with a bounding box.
+pub struct BVH<T = Surface> {
     tree: Box<[Node]>,
+    /// reordered so that each leaf refers to a contiguous range
+    primitives: Box<[T]>,
 }
 
-impl BVH {
-    pub fn from_slice(mut surfaces: Box<[Surface]>, partition_by: &PartitionBy) -> Self {
+impl<T: Hittable> BVH<T> {
+    pub fn from_slice(mut surfaces: Box<[T]>, partition_by: &PartitionBy) -> Self {
         if surfaces.is_empty() {
-            return Self { tree: Box::new([]) };
+            return Self {
+                tree: Box::new([]),
+                primitives: surfaces,
+            };
         }
 
         let tree = build_tree_rec(
             partition_by,
             Vec::with_capacity(2 * surfaces.len()),
             &mut surfaces,
+            0,
         )
         .into_boxed_slice();
 
-        Self { tree }
+        Self {
+            tree,
+            primitives: surfaces,
+        }
     }
-}
-
-fn build_tree_rec(
-    partition_by: &PartitionBy,
-    mut partial_nodes: Vec<Node>,
-    surfaces: &mut [Surface],
-) -> Vec<Node> {
-    if surfaces.len() == 1 {
-        partial_nodes.push(Node::Leaf(surfaces[0].clone()));
-    } else if surfaces.len() == 2 {
-        let (left_singleton, right_singleton) = partition_by.partition(surfaces);
-
-        let left = left_singleton[0].clone();
-        let right = right_singleton[0].clone();
-
-        partial_nodes.push(Node::Internal(
-            Some(partial_nodes.len() + 2),
-            AABB::merge(left.bounding_box(), right.bounding_box()),
-        ));
-        partial_nodes.push(Node::Leaf(left));
-        partial_nodes.push(Node::Leaf(right));
-    } else {
-        let (left, right) = partition_by.partition(surfaces);
 
-        let parent_idx = partial_nodes.len();
-        partial_nodes.push(Node::Placeholder);
-
-        partial_nodes = build_tree_rec(partition_by, partial_nodes, left);
-        let right_idx = partial_nodes.len();
-        partial_nodes = build_tree_rec(partition_by, partial_nodes, right);
-
-        partial_nodes[parent_idx] = Node::Internal(
-            Some(right_idx),
-            AABB::merge(
-                partial_nodes[parent_idx + 1].bounding_box(),
-                partial_nodes[right_idx].bounding_box(),
-            ),
-        )
+    /// The primitives the tree was built over, in the order its leaves refer to them.
+    pub fn primitives(&self) -> &[T] {
+        &self.primitives
     }
 
-    partial_nodes
-}
+    /// Like [`Hittable::hit`], but also returns the index in [`BVH::primitives`] of the
+    /// primitive that was hit.
+    pub fn hit_primitive(&self, ray: &Ray, ray_t: &Interval) -> Option<(usize, Hit, Material)> {
+        if self.tree.is_empty() {
+            return None;
+        }
 
-impl Hittable for BVH {
-    fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<(Hit, Material)> {
         let mut stack = vec![0];
-        let mut acc: Option<(Hit, Material)> = None;
+        let mut acc: Option<(usize, Hit, Material)> = None;
         let mut shrunken_ray_t = *ray_t;
 
         while let Some(i) = stack.pop() {
@@ -154,16 +182,13 @@ impl Hittable for BVH {
                         stack.push(i + 1)
                     }
                 }
-                Node::Leaf(surface) => {
-                    if let Some((hit, material)) = surface.hit(ray, &shrunken_ray_t) {
-                        if let Some((nearest_hit, nearest_material)) = acc
-                            && hit.t > nearest_hit.t
-                        {
-                            // no-op, acc is best hit
-                            acc = Some((nearest_hit, nearest_material));
-                        } else {
+                Node::Leaf(range, _) => {
+                    for index in range.clone() {
+                        let surface = &self.primitives[index];
+                        if let Some((hit, material)) = surface.hit(ray, &shrunken_ray_t) {
+                            // each hit shrinks the interval, so it's the nearest so far
                             shrunken_ray_t.max = hit.t;
-                            acc = Some((hit, material));
+                            acc = Some((index, hit, material));
                         }
                     }
                 }
@@ -172,6 +197,13 @@ impl Hittable for BVH {
 
         acc
     }
+}
+
+impl<T: Hittable> Hittable for BVH<T> {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<(Hit, Material)> {
+        self.hit_primitive(ray, ray_t)
+            .map(|(_, hit, material)| (hit, material))
+    }
 
     fn bounding_box(&self) -> AABB {
         if self.tree.is_empty() {
@@ -180,14 +212,123 @@ impl Hittable for BVH {
             self.tree[0].bounding_box()
         }
     }
+
+    fn emitters(&self) -> Vec<Surface> {
+        self.primitives.as_ref().emitters()
+    }
+}
+
+/// The top level of a two-level hierarchy: a BVH over [`Instance`]s, each of which places
+/// a bottom-level object in the world, usually a [`BVH`] of whichever primitives suit it.
+///
+/// Building one only has to look at the instances' bounding boxes, so when an object
+/// moves, rebuild this over the updated instances. Every object they share is reused
+/// as-is.
+pub type TopLevelBVH = BVH<Instance<dyn Hittable>>;
+
+/// Subtrees over at least this many primitives build their two halves in parallel. Below
+/// it, partitioning is cheap enough that handing work to another thread doesn't pay off.
+const PARALLEL_BUILD_THRESHOLD: usize = 4096;
+
+/// Builds the tree over `surfaces`, which start at index `start` of all the primitives,
+/// reordering them so that each leaf covers a contiguous range.
+fn build_tree_rec<T: Hittable>(
+    partition_by: &PartitionBy,
+    mut partial_nodes: Vec<Node>,
+    surfaces: &mut [T],
+    start: usize,
+) -> Vec<Node> {
+    let bounding_box = surfaces.as_ref().bounding_box();
+    let end = start + surfaces.len();
+
+    let split = if surfaces.len() == 1 {
+        None
+    } else {
+        partition_by.partition(surfaces)
+    };
+    let Some((left, right)) = split else {
+        partial_nodes.push(Node::Leaf(start..end, bounding_box));
+        return partial_nodes;
+    };
+
+    let parent_idx = partial_nodes.len();
+    partial_nodes.push(Node::Placeholder);
+
+    let right_start = start + left.len();
+    let right_idx = if left.len() + right.len() >= PARALLEL_BUILD_THRESHOLD {
+        // build each side on its own, then splice them in after the parent
+        let (left_nodes, right_nodes) = rayon::join(
+            || {
+                build_tree_rec(
+                    partition_by,
+                    Vec::with_capacity(2 * left.len()),
+                    left,
+                    start,
+                )
+            },
+            || {
+                let nodes = Vec::with_capacity(2 * right.len());
+                build_tree_rec(partition_by, nodes, right, right_start)
+            },
+        );
+
+        append_shifted(&mut partial_nodes, left_nodes);
+        let right_idx = partial_nodes.len();
+        append_shifted(&mut partial_nodes, right_nodes);
+        right_idx
+    } else {
+        partial_nodes = build_tree_rec(partition_by, partial_nodes, left, start);
+        let right_idx = partial_nodes.len();
+        partial_nodes = build_tree_rec(partition_by, partial_nodes, right, right_start);
+        right_idx
+    };
+
+    partial_nodes[parent_idx] = Node::Internal(Some(right_idx), bounding_box);
+    partial_nodes
+}
+
+/// Appends a subtree built in its own vector, offsetting the indices it refers to.
+fn append_shifted(partial_nodes: &mut Vec<Node>, subtree: Vec<Node>) {
+    let offset = partial_nodes.len();
+    partial_nodes.extend(subtree.into_iter().map(|node| match node {
+        Node::Internal(right_idx, bounding_box) => {
+            Node::Internal(right_idx.map(|idx| idx + offset), bounding_box)
+        }
+        node => node,
+    }));
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{geometry::Geometry, material::Material, surface::Surface, vector::Vector3};
+    use std::sync::Arc;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{
+        geometry::Geometry,
+        material::Material,
+        surface::Surface,
+        vector::{Transform, Vector3},
+    };
     use pretty_assertions::assert_eq;
 
+    fn leaf(index: usize, surface: &Surface) -> Node {
+        Node::Leaf(index..index + 1, surface.bounding_box())
+    }
+
+    /// splits all the way down, like the other strategies
+    fn sah(bucket_strategy: SAHBucketStrategy) -> PartitionBy {
+        PartitionBy::SurfaceAreaHeuristic(
+            bucket_strategy,
+            SAHLeafPolicy {
+                max_leaf_size: 1,
+                ..Default::default()
+            },
+        )
+    }
+
     #[test]
     fn test_bisect_balanced() {
         let top_left = Surface::new(
@@ -229,20 +370,24 @@ mod tests {
                 Some(3),
                 AABB::merge(bottom_left.bounding_box(), top_left.bounding_box()),
             ),
-            Node::Leaf(bottom_left.clone()),
-            Node::Leaf(top_left.clone()),
+            leaf(0, &bottom_left),
+            leaf(1, &top_left),
             // Node 4: Internal(6, bounding_box_right) - right side splits list sorted along y-axis
             Node::Internal(
                 Some(6),
                 AABB::merge(bottom_right.bounding_box(), top_right.bounding_box()),
             ),
-            Node::Leaf(bottom_right.clone()),
-            Node::Leaf(top_right.clone()),
+            leaf(2, &bottom_right),
+            leaf(3, &top_right),
         ];
 
         let actual_bvh = BVH::from_slice(Box::from(scene), &PartitionBy::LongestAxisBisectSlice);
 
-        assert_eq!(Box::from(expected_nodes), actual_bvh.tree)
+        assert_eq!(Box::from(expected_nodes), actual_bvh.tree);
+        assert_eq!(
+            Box::from([bottom_left, top_left, bottom_right, top_right]),
+            actual_bvh.primitives
+        );
     }
 
     #[test]
@@ -272,18 +417,22 @@ mod tests {
             // but because splitting [1, 2, 3] down the "middle" returns ([1], [2, 3]),
             // this tree is expectedly suboptimal.
             Node::Internal(Some(2), scene.as_slice().bounding_box()),
-            Node::Leaf(top_left.clone()),
+            leaf(0, &top_left),
             Node::Internal(
                 Some(4),
                 AABB::merge(bottom_left.bounding_box(), bottom_right.bounding_box()),
             ),
-            Node::Leaf(bottom_left.clone()),
-            Node::Leaf(bottom_right.clone()),
+            leaf(1, &bottom_left),
+            leaf(2, &bottom_right),
         ];
 
         let actual_bvh = BVH::from_slice(Box::from(scene), &PartitionBy::LongestAxisBisectSlice);
 
-        assert_eq!(Box::from(expected_nodes), actual_bvh.tree)
+        assert_eq!(Box::from(expected_nodes), actual_bvh.tree);
+        assert_eq!(
+            Box::from([top_left, bottom_left, bottom_right]),
+            actual_bvh.primitives
+        );
     }
 
     #[test]
@@ -327,20 +476,24 @@ mod tests {
                 Some(3),
                 AABB::merge(bottom_left.bounding_box(), top_left.bounding_box()),
             ),
-            Node::Leaf(bottom_left.clone()),
-            Node::Leaf(top_left.clone()),
+            leaf(0, &bottom_left),
+            leaf(1, &top_left),
             // Node 4: Internal(6, bounding_box_right) - right side splits scene at y=0
             Node::Internal(
                 Some(6),
                 AABB::merge(bottom_right.bounding_box(), top_right.bounding_box()),
             ),
-            Node::Leaf(bottom_right.clone()),
-            Node::Leaf(top_right.clone()),
+            leaf(2, &bottom_right),
+            leaf(3, &top_right),
         ];
 
         let actual_bvh = BVH::from_slice(Box::from(scene), &PartitionBy::LongestAxisMidpoint);
 
-        assert_eq!(Box::from(expected_nodes), actual_bvh.tree)
+        assert_eq!(Box::from(expected_nodes), actual_bvh.tree);
+        assert_eq!(
+            Box::from([bottom_left, top_left, bottom_right, top_right]),
+            actual_bvh.primitives
+        );
     }
 
     #[test]
@@ -368,19 +521,20 @@ mod tests {
         let expected_nodes = [
             Node::Internal(Some(2), scene.as_slice().bounding_box()),
             // expect to split into [[ground], [left, right]] first. ground is naturally less than midpoint of longest axis, y-axis.
-            Node::Leaf(ground.clone()),
+            leaf(0, &ground),
             // [left, right] longest axis is x
             Node::Internal(
                 Some(4),
                 AABB::merge(left.bounding_box(), right.bounding_box()),
             ),
-            Node::Leaf(left.clone()),
-            Node::Leaf(right.clone()),
+            leaf(1, &left),
+            leaf(2, &right),
         ];
 
         let actual_bvh = BVH::from_slice(Box::from(scene), &PartitionBy::LongestAxisMidpoint);
 
-        assert_eq!(Box::from(expected_nodes), actual_bvh.tree)
+        assert_eq!(Box::from(expected_nodes), actual_bvh.tree);
+        assert_eq!(Box::from([ground, left, right]), actual_bvh.primitives);
     }
 
     #[test]
@@ -420,7 +574,8 @@ mod tests {
                 2,
                 &[small_right.clone()].as_ref().bounding_box(),
                 1,
-                &scene.as_slice().bounding_box()
+                &scene.as_slice().bounding_box(),
+                1.0,
             ) > partition::sah::surface_area_heuristic(
                 &[small_right.clone(), large_center.clone()]
                     .as_ref()
@@ -428,7 +583,8 @@ mod tests {
                 2,
                 &[small_left.clone()].as_ref().bounding_box(),
                 1,
-                &scene.as_slice().bounding_box()
+                &scene.as_slice().bounding_box(),
+                1.0,
             )
         );
 
@@ -440,15 +596,23 @@ mod tests {
                 Some(3),
                 AABB::merge(small_left.bounding_box(), large_center.bounding_box()),
             ),
-            Node::Leaf(large_center.clone()),
-            Node::Leaf(small_left.clone()),
+            leaf(0, &large_center),
+            leaf(1, &small_left),
             // Right group: just small_right
-            Node::Leaf(small_right.clone()),
+            leaf(2, &small_right),
         ];
 
         let midpoint_bvh =
             BVH::from_slice(Box::from(scene.clone()), &PartitionBy::LongestAxisMidpoint);
         assert_eq!(Box::from(midpoint_expected), midpoint_bvh.tree);
+        assert_eq!(
+            Box::from([
+                large_center.clone(),
+                small_left.clone(),
+                small_right.clone()
+            ]),
+            midpoint_bvh.primitives
+        );
 
         let sah_expected = [
             Node::Internal(Some(4), scene.as_slice().bounding_box()),
@@ -458,23 +622,246 @@ mod tests {
                     .as_slice()
                     .bounding_box(),
             ),
-            Node::Leaf(large_center.clone()),
-            Node::Leaf(small_right.clone()),
-            Node::Leaf(small_left.clone()),
+            leaf(0, &large_center),
+            leaf(1, &small_right),
+            leaf(2, &small_left),
         ];
 
+        let sah_primitives = Box::from([large_center, small_right, small_left]);
+
         let sah_bvh_equal_buckets = BVH::from_slice(
             Box::from(scene.clone()),
-            &PartitionBy::SurfaceAreaHeuristic(SAHBucketStrategy::EqualSize(8)),
+            &sah(SAHBucketStrategy::EqualSize(8)),
         );
 
         assert_eq!(Box::from(sah_expected.clone()), sah_bvh_equal_buckets.tree);
+        assert_eq!(sah_primitives, sah_bvh_equal_buckets.primitives);
 
-        let sah_bvh_per_surface = BVH::from_slice(
-            Box::from(scene),
-            &PartitionBy::SurfaceAreaHeuristic(SAHBucketStrategy::PerSurface),
-        );
+        let sah_bvh_per_surface =
+            BVH::from_slice(Box::from(scene), &sah(SAHBucketStrategy::PerSurface));
 
         assert_eq!(Box::from(sah_expected), sah_bvh_per_surface.tree);
+        assert_eq!(sah_primitives, sah_bvh_per_surface.primitives);
+    }
+
+    #[test]
+    fn test_coincident_centroids() {
+        // a quad face split into triangles both ways, as duplicated mesh faces often are
+        let (a, b, c, d) = (
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let material = Material::Lambertian {
+            albedo: Vector3::new(0.5, 0.5, 0.5).into(),
+        };
+        let scene = [
+            Geometry::triangle(a, b - a, c - a),
+            Geometry::triangle(a, c - a, d - a),
+            Geometry::triangle(b, c - b, d - b),
+            Geometry::triangle(b, d - b, a - b),
+            Geometry::triangle(a, b - a, c - a),
+        ]
+        .map(|geometry| Surface::new(geometry, material.clone()));
+
+        let ray = Ray::new(Vector3::new(0.5, 0.5, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        for partition_by in [
+            PartitionBy::LongestAxisBisectSlice,
+            PartitionBy::LongestAxisMidpoint,
+            sah(SAHBucketStrategy::EqualSize(8)),
+            sah(SAHBucketStrategy::PerSurface),
+        ] {
+            let bvh = BVH::from_slice(Box::from(scene.clone()), &partition_by);
+
+            assert_eq!(bvh.tree.len(), 2 * scene.len() - 1);
+            assert!(bvh.hit(&ray, &Interval::new(0.0, f64::INFINITY)).is_some());
+        }
+    }
+
+    #[test]
+    fn test_top_level_matches_flattened() {
+        let sphere = |x: f64, z: f64| {
+            Surface::new(
+                Geometry::sphere(Vector3::new(x, 0.0, z), 0.5).unwrap(),
+                Material::Dielectric {
+                    refraction_index: 1.5,
+                },
+            )
+        };
+        let pair = [sphere(0.0, 0.0), sphere(1.0, 0.0)];
+        let partition_by = sah(SAHBucketStrategy::PerSurface);
+        let object: Arc<dyn Hittable> = Arc::new(BVH::from_slice(Box::from(pair), &partition_by));
+
+        let offsets = [-4.0, 0.0, 4.0];
+        let instances: Box<[Instance<dyn Hittable>]> = offsets
+            .iter()
+            .map(|&z| {
+                let transform = Transform::translation(Vector3::new(0.0, 0.0, z));
+                Instance::new(Arc::clone(&object), transform)
+            })
+            .collect();
+        let flattened: Box<[Surface]> = offsets
+            .iter()
+            .flat_map(|&z| [sphere(0.0, z), sphere(1.0, z)])
+            .collect();
+
+        let top_level = TopLevelBVH::from_slice(instances, &partition_by);
+        let flattened = BVH::from_slice(flattened, &partition_by);
+        assert_eq!(top_level.bounding_box(), flattened.bounding_box());
+
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+        for (origin, direction) in [
+            (Vector3::new(1.0, 0.0, 10.0), Vector3::new(0.0, 0.0, -1.0)),
+            (Vector3::new(-3.0, 0.2, 4.0), Vector3::new(1.0, 0.0, 0.0)),
+            (Vector3::new(0.5, 5.0, -4.0), Vector3::new(0.0, -1.0, 0.1)),
+            (Vector3::new(0.5, 5.0, -4.0), Vector3::new(0.0, 1.0, 0.0)),
+        ] {
+            let ray = Ray::new(origin, direction);
+            let expected = flattened.hit(&ray, &ray_t).map(|(hit, _)| (hit.t, hit.p));
+            let actual = top_level.hit(&ray, &ray_t).map(|(hit, _)| (hit.t, hit.p));
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_nested_bvhs() {
+        // BVHs aren't Clone, and don't need to be to go in another one
+        let sphere = |x: f64| {
+            Surface::new(
+                Geometry::sphere(Vector3::new(x, 0.0, 0.0), 0.5).unwrap(),
+                Material::Dielectric {
+                    refraction_index: 1.5,
+                },
+            )
+        };
+        let partition_by = sah(SAHBucketStrategy::EqualSize(4));
+        let clusters: Box<[BVH]> = [[0.0, 1.0, 2.0], [10.0, 11.0, 12.0], [20.0, 21.0, 22.0]]
+            .into_iter()
+            .map(|xs| BVH::from_slice(xs.map(sphere).into(), &partition_by))
+            .collect();
+        let nested = BVH::from_slice(clusters, &partition_by);
+
+        assert_eq!(nested.tree.len(), 5);
+        assert_eq!(
+            nested.bounding_box(),
+            AABB::new(Vector3::new(-0.5, -0.5, -0.5), Vector3::new(22.5, 0.5, 0.5))
+        );
+
+        let ray = Ray::new(Vector3::new(30.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0));
+        let (hit, _) = nested
+            .hit(&ray, &Interval::new(0.0, f64::INFINITY))
+            .unwrap();
+        assert_eq!(hit.p, Vector3::new(22.5, 0.0, 0.0));
+
+        let ray = Ray::new(Vector3::new(15.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0));
+        let (hit, _) = nested
+            .hit(&ray, &Interval::new(0.0, f64::INFINITY))
+            .unwrap();
+        assert_eq!(hit.p, Vector3::new(12.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_parallel_build() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0xb4);
+        let scene: Box<[Surface]> = (0..2 * PARALLEL_BUILD_THRESHOLD + 1)
+            .map(|_| {
+                Surface::new(
+                    Geometry::sphere(Vector3::random(&mut rng) * 100.0, 0.2).unwrap(),
+                    Material::Dielectric {
+                        refraction_index: 1.5,
+                    },
+                )
+            })
+            .collect();
+        let bvh = BVH::from_slice(
+            scene.clone(),
+            &PartitionBy::SurfaceAreaHeuristic(
+                SAHBucketStrategy::EqualSize(8),
+                SAHLeafPolicy::default(),
+            ),
+        );
+
+        // subtrees spliced together from other threads still point at the right children,
+        // and their leaves cover every primitive in order
+        let mut covered = 0;
+        for (i, node) in bvh.tree.iter().enumerate() {
+            match node {
+                Node::Internal(Some(right_idx), bounding_box) => {
+                    let children = AABB::merge(
+                        bvh.tree[i + 1].bounding_box(),
+                        bvh.tree[*right_idx].bounding_box(),
+                    );
+                    assert_eq!(&children, bounding_box);
+                }
+                Node::Leaf(range, _) => {
+                    assert_eq!(range.start, covered);
+                    covered = range.end;
+                }
+                _ => unreachable!(),
+            }
+        }
+        assert_eq!(covered, scene.len());
+
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+        for _ in 0..64 {
+            let ray = Ray::new(
+                Vector3::random(&mut rng) * 100.0,
+                Vector3::random_unit(&mut rng),
+            );
+            let expected = scene.as_ref().hit(&ray, &ray_t).map(|(hit, _)| hit.p);
+            let actual = bvh.hit(&ray, &ray_t).map(|(hit, _)| hit.p);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_sah_leaf_termination() {
+        let sphere = |x: f64, y: f64| {
+            Surface::new(
+                Geometry::sphere(Vector3::new(x, y, 0.0), 1.0).unwrap(),
+                Material::Dielectric {
+                    refraction_index: 1.5,
+                },
+            )
+        };
+        // two tight clusters, far apart: not worth splitting within a cluster
+        let scene: Box<[Surface]> = [0.0, 100.0]
+            .into_iter()
+            .flat_map(|x| [sphere(x, 0.0), sphere(x + 0.1, 0.0), sphere(x, 0.1)])
+            .collect();
+
+        let bvh = BVH::from_slice(
+            scene.clone(),
+            &PartitionBy::SurfaceAreaHeuristic(
+                SAHBucketStrategy::PerSurface,
+                SAHLeafPolicy::default(),
+            ),
+        );
+        let leaf_sizes = bvh
+            .tree
+            .iter()
+            .filter_map(|node| match node {
+                Node::Leaf(range, _) => Some(range.len()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(leaf_sizes, [3, 3]);
+
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+        let ray = Ray::new(Vector3::new(100.0, 0.0, 10.0), Vector3::new(0.0, 0.0, -1.0));
+        let expected = scene.as_ref().hit(&ray, &ray_t).map(|(hit, _)| hit.p);
+        assert_eq!(bvh.hit(&ray, &ray_t).map(|(hit, _)| hit.p), expected);
+
+        // a higher traversal cost tolerates bigger leaves, up to the size limit
+        let leaf_policy = SAHLeafPolicy {
+            max_leaf_size: 5,
+            traversal_cost: 100.0,
+        };
+        let bvh = BVH::from_slice(
+            scene,
+            &PartitionBy::SurfaceAreaHeuristic(SAHBucketStrategy::PerSurface, leaf_policy),
+        );
+        assert_eq!(bvh.tree.len(), 3);
     }
 }
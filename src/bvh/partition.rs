@@ -1,10 +1,6 @@
 use std::mem::swap;
 
-use crate::{
-    aabb::AABB,
-    surface::{Hittable, Surface},
-    vector::Vector3,
-};
+use crate::{aabb::AABB, surface::Hittable, vector::Vector3};
 
 #[derive(Debug, Clone, Copy)]
 enum Axis {
@@ -39,10 +35,7 @@ fn longest_axis(bounding_box: &AABB) -> &Axis {
         .unwrap() // iterator is obviously non-empty
 }
 
-fn partition_in_place(
-    surfaces: &mut [Surface],
-    pred: impl Fn(&Surface) -> bool,
-) -> (&mut [Surface], &mut [Surface]) {
+fn partition_in_place<T>(surfaces: &mut [T], pred: impl Fn(&T) -> bool) -> (&mut [T], &mut [T]) {
     let mut iter = surfaces.iter_mut();
     while let Some(left) = iter.find(|e| !pred(e)) {
         if let Some(right) = iter.rfind(|e| pred(e)) {
@@ -55,7 +48,7 @@ fn partition_in_place(
     surfaces.split_at_mut(surfaces.partition_point(pred))
 }
 
-pub fn longest_axis_bisect_slice(surfaces: &mut [Surface]) -> (&mut [Surface], &mut [Surface]) {
+pub fn longest_axis_bisect_slice<T: Hittable>(surfaces: &mut [T]) -> (&mut [T], &mut [T]) {
     let bounding_box = surfaces.as_ref().bounding_box();
     let longest_axis = longest_axis(&bounding_box);
 
@@ -67,7 +60,7 @@ pub fn longest_axis_bisect_slice(surfaces: &mut [Surface]) -> (&mut [Surface], &
     surfaces.split_at_mut(surfaces.len() / 2)
 }
 
-pub fn longest_axis_midpoint(surfaces: &mut [Surface]) -> (&mut [Surface], &mut [Surface]) {
+pub fn longest_axis_midpoint<T: Hittable>(surfaces: &mut [T]) -> (&mut [T], &mut [T]) {
     let bounding_box = surfaces.as_ref().bounding_box();
     let longest_axis = longest_axis(&bounding_box);
     let midpoint = get_component(longest_axis, &bounding_box.centroid());
@@ -80,7 +73,7 @@ pub fn longest_axis_midpoint(surfaces: &mut [Surface]) -> (&mut [Surface], &mut
 pub mod sah {
     use std::iter::zip;
 
-    use crate::interval::Interval;
+    use crate::{bvh::SAHLeafPolicy, interval::Interval};
 
     use super::*;
 
@@ -89,20 +82,21 @@ pub mod sah {
         dims.x * dims.y + dims.x * dims.z + dims.y * dims.z
     }
 
+    /// Expected cost of a ray through `bounding_box` after splitting it into `left` and
+    /// `right`, in units of testing the ray against one primitive.
     pub fn surface_area_heuristic(
         left: &AABB,
         n_left: usize,
         right: &AABB,
         n_right: usize,
         bounding_box: &AABB,
+        traversal_cost: f64,
     ) -> f64 {
         let parent_saf = surface_area_factor(bounding_box);
         let p_left = surface_area_factor(left) / parent_saf;
         let p_right = surface_area_factor(right) / parent_saf;
 
-        const ROOT_TEST_COST: f64 = 1.0;
-
-        ROOT_TEST_COST + p_left * n_left as f64 + p_right * n_right as f64
+        traversal_cost + p_left * n_left as f64 + p_right * n_right as f64
     }
 
     #[derive(Debug, Clone)]
@@ -115,12 +109,10 @@ pub mod sah {
         interval: Interval,
     }
 
-    /// splits[n_left] = (surfaces[..n_left].bounding_box(), surfaces[n_left..].bounding_box())
-    fn splits_cache(surfaces: &[Surface], axis: &Axis) -> (f64, f64, Box<[SplitVolumes]>) {
-        let mut sorted_boxes = Vec::from(surfaces)
-            .into_iter()
-            .map(|surface| surface.bounding_box())
-            .collect::<Vec<_>>();
+    /// splits[n_left] = (boxes[..n_left] merged, boxes[n_left..] merged), once `boxes` is
+    /// sorted along `axis`
+    fn splits_cache(boxes: &[AABB], axis: &Axis) -> (f64, f64, Box<[SplitVolumes]>) {
+        let mut sorted_boxes = boxes.to_vec();
 
         sorted_boxes.sort_unstable_by(|a, b| {
             let a = get_component(axis, &a.centroid());
@@ -170,14 +162,25 @@ pub mod sah {
         )
     }
 
-    fn partition_impl<'s>(
-        surfaces: &'s mut [Surface],
+    /// The cheapest split among `splitting_planes`, or `None` if `surfaces` are better off
+    /// left together in one leaf.
+    fn partition_impl<'s, T: Hittable>(
+        surfaces: &'s mut [T],
         splitting_planes: impl Iterator<Item = (&'s Axis, f64)>,
-    ) -> (&'s mut [Surface], &'s mut [Surface]) {
+        leaf_policy: &SAHLeafPolicy,
+    ) -> Option<(&'s mut [T], &'s mut [T])> {
+        let may_be_leaf = surfaces.len() <= leaf_policy.max_leaf_size;
+
         let split_at = {
-            let x_splits = splits_cache(surfaces, &Axis::X);
-            let y_splits = splits_cache(surfaces, &Axis::Y);
-            let z_splits = splits_cache(surfaces, &Axis::Z);
+            // bounding boxes can take some work to find (e.g. for mesh triangles), so only
+            // do it once for all three axes
+            let boxes = surfaces
+                .iter()
+                .map(|surface| surface.bounding_box())
+                .collect::<Box<_>>();
+            let x_splits = splits_cache(&boxes, &Axis::X);
+            let y_splits = splits_cache(&boxes, &Axis::Y);
+            let z_splits = splits_cache(&boxes, &Axis::Z);
 
             move |axis: &Axis, intercept: f64| {
                 let (min, max, splits) = match axis {
@@ -199,7 +202,7 @@ pub mod sah {
 
         let bounding_box = surfaces.as_ref().bounding_box();
 
-        let (axis, split, _cost) = splitting_planes
+        let best = splitting_planes
             .filter_map(|(axis, split)| {
                 let SplitVolumes {
                     left,
@@ -212,24 +215,44 @@ pub mod sah {
                 Some((
                     axis,
                     split,
-                    surface_area_heuristic(&left, n_left, &right, n_right, &bounding_box),
+                    surface_area_heuristic(
+                        &left,
+                        n_left,
+                        &right,
+                        n_right,
+                        &bounding_box,
+                        leaf_policy.traversal_cost,
+                    ),
                 ))
             })
-            .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
-            .expect("No valid splitting plane");
+            .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b));
+        let Some((axis, split, cost)) = best else {
+            // every centroid coincides, so no plane separates them
+            if may_be_leaf {
+                return None;
+            }
+            let mid = surfaces.len() / 2;
+            return Some(surfaces.split_at_mut(mid));
+        };
+
+        // a leaf tests a ray against every primitive in it
+        if may_be_leaf && cost >= surfaces.len() as f64 {
+            return None;
+        }
 
-        partition_in_place(surfaces, |surface| {
+        Some(partition_in_place(surfaces, |surface| {
             get_component(axis, &surface.bounding_box().centroid()) <= split
-        })
+        }))
     }
 
     pub mod equal_size {
         use super::*;
 
-        pub fn partition(
-            surfaces: &mut [Surface],
+        pub fn partition<'s, T: Hittable>(
+            surfaces: &'s mut [T],
             buckets: u32,
-        ) -> (&mut [Surface], &mut [Surface]) {
+            leaf_policy: &SAHLeafPolicy,
+        ) -> Option<(&'s mut [T], &'s mut [T])> {
             let bounding_box = surfaces.as_ref().bounding_box();
 
             let splitting_planes = Axis::ALL
@@ -244,14 +267,17 @@ pub mod sah {
                 })
                 .collect::<Vec<_>>();
 
-            partition_impl(surfaces, splitting_planes.into_iter())
+            partition_impl(surfaces, splitting_planes.into_iter(), leaf_policy)
         }
     }
 
     pub mod per_surface {
         use super::*;
 
-        pub fn partition(surfaces: &mut [Surface]) -> (&mut [Surface], &mut [Surface]) {
+        pub fn partition<'s, T: Hittable>(
+            surfaces: &'s mut [T],
+            leaf_policy: &SAHLeafPolicy,
+        ) -> Option<(&'s mut [T], &'s mut [T])> {
             let splitting_planes = surfaces
                 .iter()
                 .flat_map(|surface| {
@@ -262,7 +288,7 @@ pub mod sah {
                 })
                 .collect::<Vec<_>>();
 
-            partition_impl(surfaces, splitting_planes.into_iter())
+            partition_impl(surfaces, splitting_planes.into_iter(), leaf_policy)
         }
     }
 }
@@ -1,11 +1,16 @@
-use rand::random;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use rayon::prelude::*;
 
 use crate::{
-    interval::Interval,
+    color::white_balance_gain,
+    image::Image,
+    integrator::PathTracer,
+    light::Lights,
     ray::Ray,
+    sampling,
     surface::Hittable,
-    vector::{Vector3, cross},
+    vector::{Vector3, cross, dot},
 };
 
 pub struct Camera {
@@ -13,6 +18,8 @@ pub struct Camera {
     pub image_width: u32,
     pub samples_per_pixel: u32,
     pub max_depth: u32,
+    /// Bounces after which paths may be terminated early by Russian roulette.
+    pub roulette_depth: u32,
 
     pub v_fov: f64,
     pub look_from: Vector3,
@@ -23,14 +30,27 @@ pub struct Camera {
     pub focus_dist: f64,
 
     pub background: Vector3,
+
+    /// Exposure compensation in stops; each stop doubles the recorded radiance.
+    pub exposure: f64,
+    /// Color temperature (in kelvin) of the illuminant that should come out neutral,
+    /// e.g. 3200 for tungsten. `None` leaves colors as rendered.
+    pub white_balance: Option<f64>,
+    /// Strength of cos^4 lens falloff toward the image corners, from 0 (none) to 1 (physical).
+    pub vignetting: f64,
+
+    /// Seed for every random decision made while rendering. Renders with the same seed
+    /// are bit-identical, however rayon schedules them; `None` picks a fresh seed each time.
+    pub seed: Option<u64>,
 }
 
 pub struct InitializedCamera {
     image_width: u32,
     samples_per_pixel: u32,
-    max_depth: u32,
     defocus_angle: f64,
-    background: Vector3,
+    integrator: PathTracer,
+    vignetting: f64,
+    seed: u64,
 
     image_height: u32,
     pixel_samples_scale: f64,
@@ -40,6 +60,10 @@ pub struct InitializedCamera {
     pixel_dv: Vector3,
     defocus_disk_u: Vector3,
     defocus_disk_v: Vector3,
+    /// unit vector pointing from the camera toward the scene
+    view_direction: Vector3,
+    /// per-channel gain combining exposure and white balance
+    film_gain: Vector3,
 }
 
 impl Default for Camera {
@@ -49,6 +73,7 @@ impl Default for Camera {
             image_width: 100,
             samples_per_pixel: 10,
             max_depth: 10,
+            roulette_depth: 5,
             v_fov: 90.0,
             look_from: Vector3::ZERO,
             look_at: Vector3::new(0.0, 0.0, -1.0),
@@ -56,6 +81,10 @@ impl Default for Camera {
             defocus_angle: 0.0,
             focus_dist: 10.0,
             background: Vector3::ZERO,
+            exposure: 0.0,
+            white_balance: None,
+            vignetting: 0.0,
+            seed: None,
         }
     }
 }
@@ -95,12 +124,22 @@ impl Camera {
         let defocus_disk_u = u * defocus_radius;
         let defocus_disk_v = v * defocus_radius;
 
+        let white_balance = self
+            .white_balance
+            .map_or(Vector3::new(1.0, 1.0, 1.0), white_balance_gain);
+        let film_gain = white_balance * 2f64.powf(self.exposure);
+
         InitializedCamera {
             image_width: self.image_width,
             samples_per_pixel: self.samples_per_pixel,
-            max_depth: self.max_depth,
             defocus_angle: self.defocus_angle,
-            background: self.background,
+            integrator: PathTracer {
+                max_depth: self.max_depth,
+                roulette_depth: self.roulette_depth,
+                background: self.background,
+            },
+            vignetting: self.vignetting,
+            seed: self.seed.unwrap_or_else(rand::random),
             image_height,
             center,
             pixel00_loc,
@@ -109,59 +148,70 @@ impl Camera {
             pixel_samples_scale,
             defocus_disk_u,
             defocus_disk_v,
+            view_direction: -w,
+            film_gain,
         }
     }
 }
 
-struct Pixel {
-    pub ord: u32,
-    pub color: Vector3,
-}
+impl InitializedCamera {
+    pub fn render(&self, world: &impl Hittable, lights: &Lights) -> Image {
+        let mut image = Image::new(self.image_width, self.image_height);
+
+        image
+            .pixels_mut()
+            .par_chunks_mut(self.image_width as usize)
+            .enumerate()
+            .for_each(|(row, pixels)| {
+                pixels.par_iter_mut().enumerate().for_each(|(col, pixel)| {
+                    let (col, row) = (col as u32, row as u32);
+                    *pixel = self.develop(col, row, self.sample_pixel(world, lights, col, row));
+                })
+            });
 
-impl Pixel {
-    pub fn new(ord: u32, color: Vector3) -> Self {
-        Self { ord, color }
+        image
     }
-}
 
-impl InitializedCamera {
-    pub fn render(&self, world: &impl Hittable) {
-        println!("P3");
-        println!("{} {}", self.image_width, self.image_height);
-        println!("{}", 255);
-
-        let mut pixels = (0..self.image_height)
-            .into_par_iter()
-            .flat_map(|row| {
-                (0..self.image_width).into_par_iter().map({
-                    move |col| {
-                        Pixel::new(
-                            row * self.image_width + col,
-                            (0..self.samples_per_pixel)
-                                .into_par_iter()
-                                .map(|_| sample_square())
-                                .map(|offset| self.get_ray(col, row, offset))
-                                .map(|ray| ray_color(&ray, world, self.max_depth, self.background))
-                                .reduce(|| Vector3::ZERO, |acc, e| acc + e)
-                                * self.pixel_samples_scale,
-                        )
-                    }
-                })
+    /// Samples are accumulated sequentially so the floating point sum doesn't depend
+    /// on how rayon splits the work.
+    fn sample_pixel(&self, world: &impl Hittable, lights: &Lights, col: u32, row: u32) -> Vector3 {
+        let pixel_rng = self.pixel_rng(col, row);
+
+        (0..self.samples_per_pixel)
+            .map(|sample| {
+                let mut rng = pixel_rng.clone();
+                // each sample gets its own 2^32-word window of the pixel's stream
+                rng.set_word_pos(u128::from(sample) << 32);
+
+                let ray = self.get_ray(col, row, sample_square(&mut rng), &mut rng);
+                self.integrator.radiance(ray, world, lights, &mut rng)
             })
-            .collect::<Vec<Pixel>>();
+            .fold(Vector3::ZERO, |acc, e| acc + e)
+            * self.pixel_samples_scale
+    }
 
-        pixels.par_sort_unstable_by_key(|pixel| pixel.ord);
+    /// A ChaCha stream keyed by the seed and unique to this pixel.
+    fn pixel_rng(&self, col: u32, row: u32) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream(u64::from(row) * u64::from(self.image_width) + u64::from(col));
+        rng
+    }
 
-        let body = pixels
-            .iter()
-            .map(|pixel| ppm_pixel(pixel.color))
-            .collect::<Vec<String>>()
-            .join("\n");
+    /// Applies the film response to a pixel's accumulated radiance.
+    fn develop(&self, col: u32, row: u32, radiance: Vector3) -> Vector3 {
+        let falloff = if self.vignetting > 0.0 {
+            let pixel_center =
+                self.pixel00_loc + (col as f64 * self.pixel_du) + (row as f64 * self.pixel_dv);
+            let cos_theta = dot((pixel_center - self.center).to_unit(), self.view_direction);
+            1.0 - self.vignetting * (1.0 - cos_theta.powi(4))
+        } else {
+            1.0
+        };
 
-        println!("{body}");
+        radiance * self.film_gain * falloff
     }
 
-    fn get_ray(&self, col: u32, row: u32, offset: Vector3) -> Ray {
+    fn get_ray<R: Rng + ?Sized>(&self, col: u32, row: u32, offset: Vector3, rng: &mut R) -> Ray {
         let pixel_sample = self.pixel00_loc
             + ((col as f64 + offset.x) * self.pixel_du)
             + ((row as f64 + offset.y) * self.pixel_dv);
@@ -169,7 +219,7 @@ impl InitializedCamera {
         let origin = if self.defocus_angle <= 0.0 {
             self.center
         } else {
-            let p = Vector3::random_in_unit_disk();
+            let p = sampling::concentric_disk(rng);
             self.center + (p.x * self.defocus_disk_u) + (p.y * self.defocus_disk_v)
         };
 
@@ -177,90 +227,99 @@ impl InitializedCamera {
     }
 }
 
-fn sample_square() -> Vector3 {
-    Vector3::new(random::<f64>() - 0.5, random::<f64>() - 0.5, 0.0)
+fn sample_square<R: Rng + ?Sized>(rng: &mut R) -> Vector3 {
+    Vector3::new(rng.random::<f64>() - 0.5, rng.random::<f64>() - 0.5, 0.0)
 }
 
-fn ray_color(
-    ray: &Ray,
-    world: &impl Hittable,
-    remaining_ray_bounces: u32,
-    background: Vector3,
-) -> Vector3 {
-    if remaining_ray_bounces == 0 {
-        return Vector3::ZERO;
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{geometry::Geometry, material::Material, surface::Surface};
 
-    if let Some((hit, material)) = world.hit(&ray, &Interval::new(0.001, f64::INFINITY)) {
-        let emitted = material.emitted(&ray, &hit);
-        return match material.scatter(&ray, &hit) {
-            Some(scatter) => {
-                let scattered =
-                    ray_color(&scatter.ray, world, remaining_ray_bounces - 1, background)
-                        * scatter.attenuation;
-                emitted + scattered
-            }
-            None => emitted,
-        };
+    fn assert_near(actual: Vector3, expected: Vector3) {
+        assert!(
+            (actual - expected).length() < 1e-12,
+            "expected {expected:?}, got {actual:?}"
+        );
     }
 
-    background
-}
-
-#[allow(dead_code, unreachable_code, unused_variables)]
-fn ray_color_iterative(ray: Ray, world: &impl Hittable, max_ray_bounces: u32) -> Vector3 {
-    todo!("account for emitting materials");
-
-    let mut next_ray = ray;
-    let mut total_attenuation = Vector3::new(1.0, 1.0, 1.0);
-    let mut computed_bounces = 0;
-
-    loop {
-        if computed_bounces == max_ray_bounces {
-            return Vector3::ZERO;
+    #[test]
+    fn test_render_is_independent_of_thread_count() {
+        let world = [
+            Surface::new(
+                Geometry::sphere(Vector3::new(0.0, 0.0, -2.0), 0.5).unwrap(),
+                Material::Lambertian {
+                    albedo: Vector3::new(0.5, 0.6, 0.7).into(),
+                },
+            ),
+            Surface::new(
+                Geometry::quadrilateral(
+                    Vector3::new(-1.0, 2.0, -3.0),
+                    Vector3::new(2.0, 0.0, 0.0),
+                    Vector3::new(0.0, 0.0, 2.0),
+                ),
+                Material::DiffuseLight {
+                    emit: Vector3::new(4.0, 4.0, 4.0).into(),
+                },
+            ),
+        ];
+        let lights = Lights::from_surfaces(&world);
+        let camera = Camera {
+            image_width: 12,
+            samples_per_pixel: 8,
+            background: Vector3::new(0.1, 0.1, 0.2),
+            seed: Some(1257),
+            ..Default::default()
         }
+        .initialize();
+
+        let render_on = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| camera.render(&world.as_slice(), &lights))
+        };
 
-        if let Some((hit, material)) = world.hit(&next_ray, &Interval::new(0.001, f64::INFINITY)) {
-            if let Some(scatter) = material.scatter(&next_ray, &hit) {
-                computed_bounces += 1;
-                total_attenuation *= scatter.attenuation;
-                next_ray = scatter.ray;
-            } else {
-                return Vector3::ZERO;
-            }
-        } else {
-            break;
-        }
+        let single = render_on(1);
+        assert_eq!(single, render_on(4));
+        assert_eq!(single, render_on(7));
     }
 
-    blue_white_gradient(next_ray) * total_attenuation
-}
-
-fn blue_white_gradient(ray: Ray) -> Vector3 {
-    let alpha = (ray.direction.to_unit().y + 1.0) * 0.5;
-
-    let white = Vector3::new(1.0, 1.0, 1.0);
-    let blue = Vector3::new(0.5, 0.7, 1.0);
+    #[test]
+    fn test_exposure_and_vignetting() {
+        let background = Vector3::new(0.25, 0.5, 0.125);
+        let render = |exposure, vignetting| {
+            Camera {
+                image_width: 3,
+                samples_per_pixel: 1,
+                background,
+                exposure,
+                vignetting,
+                seed: Some(0),
+                ..Default::default()
+            }
+            .initialize()
+            .render(&<&[Surface]>::default(), &Lights::default())
+        };
 
-    (1.0 - alpha) * white + alpha * blue
-}
+        for pixel in render(1.0, 0.0).pixels() {
+            assert_near(*pixel, background * 2.0);
+        }
+        for pixel in render(-2.0, 0.0).pixels() {
+            assert_near(*pixel, background * 0.25);
+        }
 
-fn linear_to_gamma(linear_component: f64) -> f64 {
-    if linear_component > 0.0 {
-        linear_component.sqrt()
-    } else {
-        0.0
+        // the center pixel looks straight ahead, and the corners are 20 / 3 off axis both
+        // ways at a focus distance of 10
+        let cos_corner = 10.0 / (100.0_f64 + 2.0 * (20.0_f64 / 3.0).powi(2)).sqrt();
+        let full = render(0.0, 1.0);
+        let half = render(0.0, 0.5);
+        assert_near(full.pixels()[4], background);
+        assert_near(full.pixels()[0], background * cos_corner.powi(4));
+        assert_near(
+            half.pixels()[8],
+            background * (1.0 - 0.5 * (1.0 - cos_corner.powi(4))),
+        );
     }
 }
-
-fn ppm_pixel(color: Vector3) -> String {
-    let (r, g, b) = (color.x, color.y, color.z);
-    let (r, g, b) = (linear_to_gamma(r), linear_to_gamma(g), linear_to_gamma(b));
-
-    let intensity = Interval::new(0.0, 0.999);
-    let ir = (255.999 * intensity.clamp(r)) as u8;
-    let ig = (255.999 * intensity.clamp(g)) as u8;
-    let ib = (255.999 * intensity.clamp(b)) as u8;
-
-    format!("{ir} {ig} {ib}")
-}
@@ -0,0 +1,301 @@
+use thiserror::Error;
+
+use crate::vector::{Vector3, dot};
+
+/// Relative luminance weights for linear Rec. 709 primaries.
+const LUMINANCE: Vector3 = Vector3::new(0.2126, 0.7152, 0.0722);
+
+pub fn luminance(color: Vector3) -> f64 {
+    dot(color, LUMINANCE)
+}
+
+/// Linear sRGB color of a black body at `kelvin`, scaled to unit luminance.
+///
+/// Uses Kim et al.'s cubic fit of the Planckian locus, valid from 1667K to 25000K;
+/// temperatures outside that range are clamped to it.
+pub fn blackbody(kelvin: f64) -> Vector3 {
+    let t = kelvin.clamp(1667.0, 25000.0);
+
+    let x = if t <= 4000.0 {
+        -0.266_123_9e9 / t.powi(3) - 0.234_358_9e6 / t.powi(2) + 0.877_695_6e3 / t + 0.179_910
+    } else {
+        -3.025_846_9e9 / t.powi(3) + 2.107_037_9e6 / t.powi(2) + 0.222_634_7e3 / t + 0.240_390
+    };
+
+    let y = if t <= 2222.0 {
+        -1.106_381_4 * x.powi(3) - 1.348_110_20 * x.powi(2) + 2.185_558_32 * x - 0.202_196_83
+    } else if t <= 4000.0 {
+        -0.954_947_6 * x.powi(3) - 1.374_185_93 * x.powi(2) + 2.091_370_15 * x - 0.167_488_67
+    } else {
+        3.081_758_0 * x.powi(3) - 5.873_386_70 * x.powi(2) + 3.751_129_97 * x - 0.370_014_83
+    };
+
+    // CIE xyY with Y = 1 to XYZ, then to linear sRGB
+    let (cx, cy, cz) = (x / y, 1.0, (1.0 - x - y) / y);
+    Vector3::new(
+        3.240_454_2 * cx - 1.537_138_5 * cy - 0.498_531_4 * cz,
+        -0.969_266_0 * cx + 1.876_010_8 * cy + 0.041_556_0 * cz,
+        0.055_643_4 * cx - 0.204_025_9 * cy + 1.057_225_2 * cz,
+    )
+}
+
+/// Per-channel gain that renders light from a black body at `kelvin` as neutral gray,
+/// scaled so the gain itself has unit luminance.
+///
+/// Below roughly 1900K the illuminant falls outside the sRGB gamut, in which case its
+/// negative channels are clipped and the result is only approximately neutral.
+pub fn white_balance_gain(kelvin: f64) -> Vector3 {
+    const MIN_CHANNEL: f64 = 1e-3;
+
+    let illuminant = blackbody(kelvin);
+    let gain = Vector3::new(
+        1.0 / illuminant.x.max(MIN_CHANNEL),
+        1.0 / illuminant.y.max(MIN_CHANNEL),
+        1.0 / illuminant.z.max(MIN_CHANNEL),
+    );
+    gain / luminance(gain)
+}
+
+/// Operators compressing linear radiance into the displayable [0, 1] range.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ToneMapper {
+    /// Clip each channel to [0, 1].
+    Clamp,
+
+    /// Reinhard's `L / (1 + L)`, applied to luminance so hues are preserved.
+    Reinhard,
+
+    /// Reinhard with `white_point` (in scene luminance) mapped to 1.0 rather than infinity.
+    ReinhardExtended { white_point: f64 },
+
+    /// John Hable's filmic curve from Uncharted 2, normalized so `white_point` maps to 1.0.
+    Hable { white_point: f64 },
+
+    /// Krzysztof Narkowicz's fit of the ACES reference rendering transform.
+    Aces,
+}
+
+#[derive(Error, Debug)]
+pub enum ConstructToneMapperError {
+    #[error("invalid white point {0} (expected a positive, finite luminance)")]
+    InvalidWhitePoint(f64),
+}
+
+impl ToneMapper {
+    /// Checks that any white point is one the curve can be normalized by.
+    pub fn validate(&self) -> Result<(), ConstructToneMapperError> {
+        match *self {
+            ToneMapper::ReinhardExtended { white_point } | ToneMapper::Hable { white_point }
+                if !(white_point.is_finite() && white_point > 0.0) =>
+            {
+                Err(ConstructToneMapperError::InvalidWhitePoint(white_point))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn map(&self, color: Vector3) -> Vector3 {
+        match *self {
+            ToneMapper::Clamp => color,
+            ToneMapper::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMapper::ReinhardExtended { white_point } => {
+                scale_luminance(color, |l| l * (1.0 + l / white_point.powi(2)) / (1.0 + l))
+            }
+            ToneMapper::Hable { white_point } => {
+                let white_scale = 1.0 / hable_partial(white_point);
+                per_channel(color, |x| hable_partial(x) * white_scale)
+            }
+            ToneMapper::Aces => per_channel(color, |x| {
+                const A: f64 = 2.51;
+                const B: f64 = 0.03;
+                const C: f64 = 2.43;
+                const D: f64 = 0.59;
+                const E: f64 = 0.14;
+
+                (x * (A * x + B)) / (x * (C * x + D) + E)
+            }),
+        }
+    }
+}
+
+fn per_channel(color: Vector3, f: impl Fn(f64) -> f64) -> Vector3 {
+    Vector3::new(f(color.x), f(color.y), f(color.z))
+}
+
+fn scale_luminance(color: Vector3, f: impl Fn(f64) -> f64) -> Vector3 {
+    let l = luminance(color);
+    if l <= 0.0 {
+        Vector3::ZERO
+    } else {
+        color * (f(l) / l)
+    }
+}
+
+fn hable_partial(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+/// Encodings from linear [0, 1] values to what gets stored in a display-referred image.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransferFunction {
+    Linear,
+    /// The piecewise sRGB curve (IEC 61966-2-1).
+    Srgb,
+    /// A pure power law, `x^(1 / gamma)`. Prefer [`TransferFunction::gamma`], which checks
+    /// that `gamma` is positive and finite.
+    Gamma(f64),
+}
+
+#[derive(Error, Debug)]
+pub enum ConstructTransferFunctionError {
+    #[error("invalid gamma {0} (expected a positive, finite gamma)")]
+    InvalidGamma(f64),
+}
+
+impl TransferFunction {
+    pub fn gamma(gamma: f64) -> Result<Self, ConstructTransferFunctionError> {
+        let transfer = TransferFunction::Gamma(gamma);
+        transfer.validate()?;
+        Ok(transfer)
+    }
+
+    /// Checks the parameters of a transfer function built directly from its variant.
+    pub fn validate(&self) -> Result<(), ConstructTransferFunctionError> {
+        match *self {
+            TransferFunction::Gamma(gamma) if !(gamma.is_finite() && gamma > 0.0) => {
+                Err(ConstructTransferFunctionError::InvalidGamma(gamma))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn encode(&self, linear: f64) -> f64 {
+        let linear = linear.max(0.0);
+        match *self {
+            TransferFunction::Linear => linear,
+            TransferFunction::Srgb => {
+                if linear <= 0.003_130_8 {
+                    12.92 * linear
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                }
+            }
+            TransferFunction::Gamma(gamma) => linear.powf(1.0 / gamma),
+        }
+    }
+
+    pub fn decode(&self, encoded: f64) -> f64 {
+        let encoded = encoded.max(0.0);
+        match *self {
+            TransferFunction::Linear => encoded,
+            TransferFunction::Srgb => {
+                if encoded <= 0.040_45 {
+                    encoded / 12.92
+                } else {
+                    ((encoded + 0.055) / 1.055).powf(2.4)
+                }
+            }
+            TransferFunction::Gamma(gamma) => encoded.powf(gamma),
+        }
+    }
+}
+
+/// How linear radiance is turned into display values by the low dynamic range writers.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DisplayTransform {
+    pub tone_mapper: ToneMapper,
+    pub transfer: TransferFunction,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self {
+            tone_mapper: ToneMapper::Clamp,
+            transfer: TransferFunction::Srgb,
+        }
+    }
+}
+
+impl DisplayTransform {
+    /// Tone maps and encodes `color`, clamped to [0, 1].
+    pub fn apply(&self, color: Vector3) -> Vector3 {
+        per_channel(self.tone_mapper.map(color), |x| {
+            self.transfer.encode(x.clamp(0.0, 1.0)).clamp(0.0, 1.0)
+        })
+    }
+
+    pub fn to_rgb8(&self, color: Vector3) -> [u8; 3] {
+        let encoded = self.apply(color);
+        [encoded.x, encoded.y, encoded.z].map(|x| (x * 255.0).round() as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_round_trip() {
+        for i in 0..=100 {
+            let x = f64::from(i) / 100.0;
+            let round_trip = TransferFunction::Srgb.decode(TransferFunction::Srgb.encode(x));
+            assert!((round_trip - x).abs() < 1e-12, "{x} -> {round_trip}");
+        }
+    }
+
+    #[test]
+    fn test_gamma_must_be_positive() {
+        for gamma in [0.0, -2.2, f64::NAN, f64::INFINITY] {
+            assert!(TransferFunction::gamma(gamma).is_err(), "{gamma}");
+        }
+        let gamma = TransferFunction::gamma(2.2).unwrap();
+        assert!((gamma.decode(gamma.encode(0.25)) - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_white_point_must_be_positive() {
+        for white_point in [0.0, -4.0, f64::NAN, f64::INFINITY] {
+            for tone_mapper in [
+                ToneMapper::ReinhardExtended { white_point },
+                ToneMapper::Hable { white_point },
+            ] {
+                assert!(tone_mapper.validate().is_err(), "{tone_mapper:?}");
+            }
+        }
+        assert!(ToneMapper::Hable { white_point: 4.0 }.validate().is_ok());
+        assert!(ToneMapper::Aces.validate().is_ok());
+    }
+
+    #[test]
+    fn test_white_balance_neutralizes_illuminant() {
+        for kelvin in [2000.0, 3200.0, 5500.0, 9000.0] {
+            let balanced = blackbody(kelvin) * white_balance_gain(kelvin);
+            assert!(
+                (balanced.x - balanced.y).abs() < 1e-9 && (balanced.y - balanced.z).abs() < 1e-9,
+                "{kelvin}K -> {balanced:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_white_point_maps_to_one() {
+        let white = Vector3::new(4.0, 4.0, 4.0);
+        for tone_mapper in [
+            ToneMapper::ReinhardExtended { white_point: 4.0 },
+            ToneMapper::Hable { white_point: 4.0 },
+        ] {
+            let mapped = tone_mapper.map(white);
+            assert!(
+                (mapped.x - 1.0).abs() < 1e-12,
+                "{tone_mapper:?} -> {mapped:?}"
+            );
+        }
+    }
+}
@@ -1,10 +1,12 @@
+use rand::Rng;
 use thiserror::Error;
 
 use crate::{
     aabb::AABB,
     interval::Interval,
     ray::Ray,
-    vector::{Vector3, cross, dot},
+    sampling,
+    vector::{Transform, Vector3, cross, dot},
 };
 
 #[derive(Clone)]
@@ -19,7 +21,11 @@ pub struct Hit {
 
     /// whether the ray hit the "outward" face of this surface
     pub front_face: bool,
+    /// the true surface normal, facing against the ray
     pub face_normal: Vector3,
+    /// the normal to shade with, on the same side as `face_normal`. Differs from it only
+    /// where vertex normals are interpolated across a triangle
+    pub shading_normal: Vector3,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -27,6 +33,11 @@ pub enum Geometry {
     Sphere {
         center: Vector3,
         radius: f64,
+        /// unit vector toward the north pole, where beta = 1
+        pole: Vector3,
+        /// unit vector perpendicular to `pole`, toward where alpha = 0.5 on the equator.
+        /// the seam (alpha = 0 or 1) is on the opposite side
+        meridian: Vector3,
     },
     Quadrilateral {
         q: Vector3,
@@ -43,6 +54,8 @@ pub enum Geometry {
         norm: Vector3,
         d: f64,
         w: Vector3,
+        /// outward unit normals at `q`, `q + u` and `q + v`, for smooth shading
+        normals: Option<Box<[Vector3; 3]>>,
     },
 }
 
@@ -50,15 +63,50 @@ pub enum Geometry {
 pub enum ConstructSphereError {
     #[error("invalid radius {0} (expected non-negative radius)")]
     NonnegativeRadius(f64),
+    #[error("invalid pole axis {0:?} (expected a non-zero vector)")]
+    ZeroPole(Vector3),
 }
 
 impl Geometry {
+    /// A sphere with its pole along +y, and its seam facing -x.
     pub fn sphere(center: Vector3, radius: f64) -> Result<Self, ConstructSphereError> {
+        Self::oriented_sphere(center, radius, Vector3::new(0.0, 1.0, 0.0), 0.0)
+    }
+
+    /// A sphere whose texture coordinates wrap around `pole`, and are then turned by
+    /// `seam_rotation` radians about it, counterclockwise looking down on the pole.
+    ///
+    /// Before turning, the seam faces away from +x, or from +z if the pole leans more
+    /// toward x than z, matching [`Geometry::sphere`] when the pole is +y.
+    pub fn oriented_sphere(
+        center: Vector3,
+        radius: f64,
+        pole: Vector3,
+        seam_rotation: f64,
+    ) -> Result<Self, ConstructSphereError> {
         if radius < 0.0 {
-            Err(ConstructSphereError::NonnegativeRadius(radius))
-        } else {
-            Ok(Geometry::Sphere { center, radius })
+            return Err(ConstructSphereError::NonnegativeRadius(radius));
+        }
+        if pole.length_squared() == 0.0 {
+            return Err(ConstructSphereError::ZeroPole(pole));
         }
+
+        let pole = pole.to_unit();
+        let reference = if pole.x.abs() > pole.z.abs() {
+            Vector3::new(0.0, 0.0, 1.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        // project out the pole, then rotate about it
+        let meridian = (reference - dot(reference, pole) * pole).to_unit();
+        let meridian = meridian * seam_rotation.cos() + cross(pole, meridian) * seam_rotation.sin();
+
+        Ok(Geometry::Sphere {
+            center,
+            radius,
+            pole,
+            meridian,
+        })
     }
 
     pub fn quadrilateral(q: Vector3, u: Vector3, v: Vector3) -> Self {
@@ -84,12 +132,89 @@ impl Geometry {
             norm,
             d: dot(norm, q),
             w: n / dot(n, n),
+            normals: None,
+        }
+    }
+
+    /// A triangle shaded as if curved, by interpolating `normals` given at `q`, `q + u`
+    /// and `q + v`. They should point out of the same side as `cross(u, v)`; a zero normal
+    /// leaves the triangle flat.
+    pub fn smooth_triangle(q: Vector3, u: Vector3, v: Vector3, normals: [Vector3; 3]) -> Self {
+        let n = cross(u, v);
+        let norm = n.to_unit();
+        Self::Triangle {
+            q,
+            u,
+            v,
+            norm,
+            d: dot(norm, q),
+            w: n / dot(n, n),
+            normals: Some(Box::new(normals.map(vertex_normal))),
+        }
+    }
+
+    /// This geometry moved by `transform`, as a standalone copy. Triangles and
+    /// quadrilaterals stay flat under any transform, but spheres only stay spheres under
+    /// ones that scale evenly, so others give `None`.
+    pub fn transformed(&self, transform: &Transform) -> Option<Geometry> {
+        match *self {
+            Geometry::Sphere {
+                center,
+                radius,
+                pole,
+                meridian,
+            } => {
+                let [x, y, z] = [
+                    Vector3::new(1.0, 0.0, 0.0),
+                    Vector3::new(0.0, 1.0, 0.0),
+                    Vector3::new(0.0, 0.0, 1.0),
+                ]
+                .map(|axis| transform.vector(axis));
+                let scale = x.length();
+                let uniform = [y, z]
+                    .iter()
+                    .all(|axis| (axis.length() - scale).abs() <= 1e-9 * scale)
+                    && [dot(x, y), dot(y, z), dot(z, x)]
+                        .iter()
+                        .all(|cosine| cosine.abs() <= 1e-9 * scale * scale);
+                uniform.then(|| Geometry::Sphere {
+                    center: transform.point(center),
+                    radius: radius * scale,
+                    pole: transform.vector(pole).to_unit(),
+                    meridian: transform.vector(meridian).to_unit(),
+                })
+            }
+            Geometry::Quadrilateral { q, u, v, .. } => Some(Geometry::quadrilateral(
+                transform.point(q),
+                transform.vector(u),
+                transform.vector(v),
+            )),
+            Geometry::Triangle {
+                q,
+                u,
+                v,
+                ref normals,
+                ..
+            } => {
+                let (q, u, v) = (transform.point(q), transform.vector(u), transform.vector(v));
+                Some(match normals {
+                    Some(normals) => {
+                        Geometry::smooth_triangle(q, u, v, normals.map(|n| transform.normal(n)))
+                    }
+                    None => Geometry::triangle(q, u, v),
+                })
+            }
         }
     }
 
     pub fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<Hit> {
         match *self {
-            Geometry::Sphere { center, radius } => sphere::hit(center, radius, ray, ray_t),
+            Geometry::Sphere {
+                center,
+                radius,
+                pole,
+                meridian,
+            } => sphere::hit(center, radius, pole, meridian, ray, ray_t),
             Geometry::Quadrilateral {
                 q,
                 u,
@@ -105,13 +230,14 @@ impl Geometry {
                 norm,
                 d,
                 w,
-            } => triangle::hit(q, u, v, norm, d, w, ray, ray_t),
+                ref normals,
+            } => triangle::hit(q, u, v, norm, d, w, normals.as_deref(), ray, ray_t),
         }
     }
 
     pub fn bounding_box(&self) -> AABB {
         match *self {
-            Geometry::Sphere { center, radius } => sphere::bounding_box(center, radius),
+            Geometry::Sphere { center, radius, .. } => sphere::bounding_box(center, radius),
             Geometry::Quadrilateral {
                 q,
                 u,
@@ -127,9 +253,54 @@ impl Geometry {
                 norm: _,
                 d: _,
                 w: _,
+                normals: _,
             } => triangle::bounding_box(q, u, v),
         }
     }
+
+    /// Samples a direction from `origin` toward a point on this surface: uniformly by area
+    /// for planar shapes, and uniformly over the visible cone for spheres.
+    pub fn sample_direction<R: Rng + ?Sized>(&self, origin: Vector3, rng: &mut R) -> Vector3 {
+        match *self {
+            Geometry::Sphere { center, radius, .. } => {
+                sphere::sample_direction(center, radius, origin, rng)
+            }
+            Geometry::Quadrilateral { q, u, v, .. } => {
+                q + rng.random::<f64>() * u + rng.random::<f64>() * v - origin
+            }
+            Geometry::Triangle { q, u, v, .. } => {
+                let (a, b) = sampling::uniform_triangle(rng);
+                q + a * u + b * v - origin
+            }
+        }
+    }
+
+    /// Density, with respect to solid angle at `origin`, of [`Geometry::sample_direction`]
+    /// producing `direction`. Zero for directions that miss the surface.
+    pub fn direction_pdf(&self, origin: Vector3, direction: Vector3) -> f64 {
+        let ray = Ray::new(origin, direction);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+
+        self.hit(&ray, &ray_t)
+            .map_or(0.0, |hit| self.hit_direction_pdf(&ray, &hit))
+    }
+
+    /// Like [`Geometry::direction_pdf`], for the direction `ray` took to `hit`, which is
+    /// already known to be on this surface.
+    pub fn hit_direction_pdf(&self, ray: &Ray, hit: &Hit) -> f64 {
+        match *self {
+            Geometry::Sphere { center, radius, .. } => {
+                sphere::direction_pdf(center, radius, ray, hit)
+            }
+            Geometry::Quadrilateral { u, v, .. } => {
+                area_density_to_solid_angle(ray, hit, 1.0 / cross(u, v).length())
+            }
+            Geometry::Triangle { u, v, .. } => {
+                let area_pdf = sampling::uniform_triangle_pdf(cross(u, v).length() / 2.0);
+                area_density_to_solid_angle(ray, hit, area_pdf)
+            }
+        }
+    }
 }
 
 fn compute_face_normal(ray: &Ray, outward_normal: Vector3) -> (bool, Vector3) {
@@ -144,6 +315,51 @@ fn compute_face_normal(ray: &Ray, outward_normal: Vector3) -> (bool, Vector3) {
     (front_face, face_normal)
 }
 
+/// `normal` scaled to unit length for a triangle's corner, or zero if it has no direction,
+/// which leaves the triangles sharing that corner flat-shaded.
+pub(crate) fn vertex_normal(normal: Vector3) -> Vector3 {
+    let length_squared = normal.length_squared();
+    if length_squared > 0.0 && length_squared.is_finite() {
+        normal.to_unit()
+    } else {
+        Vector3::ZERO
+    }
+}
+
+/// The shading normal at barycentric `(alpha, beta)` between `normals` given at a
+/// triangle's corners, on the same side as `face_normal`, the side the ray hit.
+fn interpolate_normal(
+    normals: &[Vector3; 3],
+    alpha: f64,
+    beta: f64,
+    face_normal: Vector3,
+) -> Vector3 {
+    let [n_q, n_u, n_v] = *normals;
+    let normal = (1.0 - alpha - beta) * n_q + alpha * n_u + beta * n_v;
+    // opposing corners can cancel out, too
+    if normals.contains(&Vector3::ZERO) || normal.length_squared() < 1e-12 {
+        return face_normal;
+    }
+
+    // normals leaning away from each other can interpolate to one behind the true surface,
+    // which would have the ray arrive from inside it
+    let normal = normal.to_unit();
+    if dot(normal, face_normal) < 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+/// Converts a density over a surface's area at `hit` into a density over the solid angle
+/// seen from the ray's origin.
+fn area_density_to_solid_angle(ray: &Ray, hit: &Hit, area_pdf: f64) -> f64 {
+    let distance_squared = (hit.t * ray.direction.length()).powi(2);
+    let cosine = dot(ray.direction.to_unit(), hit.face_normal).abs();
+
+    area_pdf * distance_squared / cosine
+}
+
 fn plane_intersection(norm: Vector3, d: f64, ray: &Ray) -> Option<f64> {
     let denominator = dot(norm, ray.direction);
     if denominator.abs() < 1e-10 {
@@ -163,6 +379,7 @@ struct UvHit {
     beta: f64,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn uv_hit(
     q: Vector3,
     u: Vector3,
@@ -192,16 +409,26 @@ fn uv_hit(
 mod sphere {
     use std::f64::consts::PI;
 
+    use rand::Rng;
+
     use crate::{
         aabb::AABB,
         interval::Interval,
         ray::Ray,
-        vector::{Vector3, dot},
+        sampling,
+        vector::{ONB, Vector3, cross, dot},
     };
 
-    use super::{Hit, compute_face_normal};
+    use super::{Hit, area_density_to_solid_angle, compute_face_normal};
 
-    pub fn hit(center: Vector3, radius: f64, ray: &Ray, ray_t: &Interval) -> Option<Hit> {
+    pub fn hit(
+        center: Vector3,
+        radius: f64,
+        pole: Vector3,
+        meridian: Vector3,
+        ray: &Ray,
+        ray_t: &Interval,
+    ) -> Option<Hit> {
         let oc = center - ray.origin;
         let a = ray.direction.length_squared();
         let h = dot(ray.direction, oc);
@@ -233,11 +460,7 @@ mod sphere {
         let outward_normal = (p - center) / radius;
         let (front_face, face_normal) = compute_face_normal(ray, outward_normal);
 
-        let theta = f64::acos(-p.y);
-        let phi = f64::atan2(-p.z, p.x) + PI;
-
-        let alpha = phi / (2.0 * PI);
-        let beta = theta / PI;
+        let (alpha, beta) = uv(outward_normal, pole, meridian);
 
         Some(Hit {
             t,
@@ -246,6 +469,7 @@ mod sphere {
             beta,
             face_normal,
             front_face,
+            shading_normal: face_normal,
         })
     }
 
@@ -253,6 +477,56 @@ mod sphere {
         let radii = Vector3::new(radius, radius, radius);
         AABB::new(center + radii, center - radii)
     }
+
+    /// `1 - cos` of the half-angle of the cone the sphere subtends from `origin`,
+    /// or `None` if `origin` is inside the sphere.
+    fn cone_extent(center: Vector3, radius: f64, origin: Vector3) -> Option<f64> {
+        let sin_squared = radius.powi(2) / (center - origin).length_squared();
+        if sin_squared >= 1.0 {
+            return None;
+        }
+
+        // 1 - sqrt(1 - x), rearranged to keep precision for distant spheres
+        Some(sin_squared / (1.0 + (1.0 - sin_squared).sqrt()))
+    }
+
+    pub fn sample_direction<R: Rng + ?Sized>(
+        center: Vector3,
+        radius: f64,
+        origin: Vector3,
+        rng: &mut R,
+    ) -> Vector3 {
+        let Some(extent) = cone_extent(center, radius, origin) else {
+            // every direction sees the sphere from inside, so sample its surface by area
+            return center + radius * sampling::uniform_sphere(rng) - origin;
+        };
+
+        ONB::from_w(center - origin).to_world(sampling::uniform_cone(extent, rng))
+    }
+
+    /// Latitude-longitude coordinates of the point on the sphere in direction `normal`:
+    /// alpha runs eastward around `pole` starting from the seam, and beta from the south
+    /// pole to the north.
+    pub fn uv(normal: Vector3, pole: Vector3, meridian: Vector3) -> (f64, f64) {
+        let east = cross(pole, meridian);
+        let (x, y, z) = (dot(normal, meridian), dot(normal, pole), dot(normal, east));
+
+        let theta = f64::acos(-y.clamp(-1.0, 1.0));
+        let phi = f64::atan2(z, x) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// Density of [`sample_direction`] producing the direction `ray` took to `hit`.
+    pub fn direction_pdf(center: Vector3, radius: f64, ray: &Ray, hit: &Hit) -> f64 {
+        match cone_extent(center, radius, ray.origin) {
+            Some(extent) => sampling::uniform_cone_pdf(extent),
+            None => {
+                let area_pdf = sampling::uniform_sphere_pdf() / radius.powi(2);
+                area_density_to_solid_angle(ray, hit, area_pdf)
+            }
+        }
+    }
 }
 
 mod quad {
@@ -285,18 +559,19 @@ mod quad {
             beta,
             face_normal,
             front_face,
+            shading_normal: face_normal,
         })
     }
 
     pub fn bounding_box(q: Vector3, u: Vector3, v: Vector3) -> AABB {
-        AABB::new(q, q + u + v).padded(0.0001)
+        AABB::merge(AABB::new(q, q + u + v), AABB::new(q + u, q + v)).padded(0.0001)
     }
 }
 
 mod triangle {
     use crate::{aabb::AABB, interval::Interval, ray::Ray, vector::Vector3};
 
-    use super::{Hit, UvHit, compute_face_normal, uv_hit};
+    use super::{Hit, UvHit, compute_face_normal, interpolate_normal, uv_hit};
 
     #[allow(clippy::too_many_arguments)]
     pub fn hit(
@@ -306,6 +581,7 @@ mod triangle {
         norm: Vector3,
         d: f64,
         w: Vector3,
+        normals: Option<&[Vector3; 3]>,
         ray: &Ray,
         ray_t: &Interval,
     ) -> Option<Hit> {
@@ -316,6 +592,9 @@ mod triangle {
         }
 
         let (front_face, face_normal) = compute_face_normal(ray, norm);
+        let shading_normal = normals.map_or(face_normal, |normals| {
+            interpolate_normal(normals, alpha, beta, face_normal)
+        });
         Some(Hit {
             t,
             p,
@@ -323,6 +602,7 @@ mod triangle {
             beta,
             face_normal,
             front_face,
+            shading_normal,
         })
     }
 
@@ -330,3 +610,297 @@ mod triangle {
         AABB::merge(AABB::new(q, q + u), AABB::new(q, q + v)).padded(0.0001)
     }
 }
+
+/// Triangles of a [`TriangleMesh`], for [`MeshTriangle`](crate::mesh::MeshTriangle).
+pub(crate) mod mesh_triangle {
+    use crate::{
+        aabb::AABB,
+        interval::Interval,
+        mesh::TriangleMesh,
+        ray::Ray,
+        vector::{cross, dot},
+    };
+
+    use super::{Hit, UvHit, compute_face_normal, interpolate_normal, triangle, uv_hit};
+
+    pub fn bounding_box(mesh: &TriangleMesh, index: usize) -> AABB {
+        let [q, b, c] = mesh.vertices(index);
+        triangle::bounding_box(q, b - q, c - q)
+    }
+
+    pub fn hit(mesh: &TriangleMesh, index: usize, ray: &Ray, ray_t: &Interval) -> Option<Hit> {
+        let [q, b, c] = mesh.vertices(index);
+        let (u, v) = (b - q, c - q);
+        let n = cross(u, v);
+        let norm = n.to_unit();
+
+        let UvHit { t, p, alpha, beta } =
+            uv_hit(q, u, v, norm, dot(norm, q), n / dot(n, n), ray, ray_t)?;
+
+        if !(alpha >= 0.0 && beta >= 0.0 && alpha + beta <= 1.0) {
+            return None;
+        }
+
+        let (front_face, face_normal) = compute_face_normal(ray, norm);
+        let shading_normal = mesh.vertex_normals(index).map_or(face_normal, |normals| {
+            interpolate_normal(&normals, alpha, beta, face_normal)
+        });
+
+        // report the mesh's texture coordinates where it has them, like a plain triangle's
+        // barycentric ones otherwise
+        let (alpha, beta) = match mesh.vertex_uvs(index) {
+            Some([uv_q, uv_b, uv_c]) => {
+                let gamma = 1.0 - alpha - beta;
+                (
+                    gamma * uv_q.0 + alpha * uv_b.0 + beta * uv_c.0,
+                    gamma * uv_q.1 + alpha * uv_b.1 + beta * uv_c.1,
+                )
+            }
+            None => (alpha, beta),
+        };
+
+        Some(Hit {
+            t,
+            p,
+            alpha,
+            beta,
+            face_normal,
+            front_face,
+            shading_normal,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        material::Material,
+        mesh::{MeshTriangle, TriangleMesh},
+        surface::{Hittable, Surface},
+    };
+
+    /// (alpha, beta) where a ray from far along `direction` first meets `geometry`
+    fn uv_from(geometry: &Geometry, center: Vector3, direction: Vector3) -> (f64, f64) {
+        let ray = Ray::new(center + 1e4 * direction, -direction);
+        let hit = geometry
+            .hit(&ray, &Interval::new(0.0, f64::INFINITY))
+            .expect("ray aimed at the center should hit");
+        (hit.alpha, hit.beta)
+    }
+
+    fn gray() -> Material {
+        Material::Lambertian {
+            albedo: Vector3::new(0.5, 0.5, 0.5).into(),
+        }
+    }
+
+    /// The one triangle of a mesh with just the given corners and attributes.
+    fn single_mesh_triangle(
+        corners: [Vector3; 3],
+        normals: Option<[Vector3; 3]>,
+        uvs: Option<[(f64, f64); 3]>,
+    ) -> MeshTriangle {
+        let mesh = TriangleMesh::new(
+            corners.into(),
+            [[0, 1, 2]].into(),
+            normals.map(Box::from),
+            uvs.map(Box::from),
+        )
+        .unwrap();
+        MeshTriangle::new(Arc::new(mesh), Arc::new(gray()), 0).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_sphere_uv_ignores_center_and_radius() {
+        for (center, radius) in [
+            (Vector3::new(0.0, 0.0, 0.0), 1.0),
+            (Vector3::new(3.0, -2.0, 7.0), 1.0),
+            (Vector3::new(-5.0, 4.0, 1.0), 0.25),
+            (Vector3::new(0.0, -1000.0, 0.0), 1000.0),
+        ] {
+            let sphere = Geometry::sphere(center, radius).unwrap();
+            let uv = |x, y, z| uv_from(&sphere, center, Vector3::new(x, y, z));
+
+            for (direction, alpha) in [
+                ((1.0, 0.0, 0.0), 0.5),
+                ((0.0, 0.0, 1.0), 0.25),
+                ((0.0, 0.0, -1.0), 0.75),
+            ] {
+                let (x, y, z) = direction;
+                assert_close(uv(x, y, z).0, alpha);
+                assert_close(uv(x, y, z).1, 0.5);
+            }
+            assert_close(uv(0.0, 1.0, 0.0).1, 1.0);
+            assert_close(uv(0.0, -1.0, 0.0).1, 0.0);
+        }
+    }
+
+    #[test]
+    fn test_quad_bounds_every_corner() {
+        // a parallelogram whose q + u + v diagonal is much narrower than its other one
+        let (q, u, v) = (
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(-2.0, 1.0, 0.0),
+        );
+        let bounds = Geometry::quadrilateral(q, u, v).bounding_box();
+
+        for corner in [q, q + u, q + v, q + u + v] {
+            for axis in [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)] {
+                let along = dot(corner, axis);
+                assert!(dot(bounds.min(), axis) <= along && along <= dot(bounds.max(), axis));
+            }
+        }
+    }
+
+    #[test]
+    fn test_oriented_sphere_uv() {
+        let center = Vector3::new(1.0, 2.0, 3.0);
+        // a pole along +x starts the seam facing -z, and a quarter turn moves it to +y
+        let sphere =
+            Geometry::oriented_sphere(center, 2.0, Vector3::new(5.0, 0.0, 0.0), FRAC_PI_2).unwrap();
+        let uv = |x, y, z| uv_from(&sphere, center, Vector3::new(x, y, z));
+
+        assert_close(uv(0.0, -1.0, 0.0).0, 0.5);
+        assert_close(uv(0.0, 0.0, 1.0).0, 0.25);
+        assert_close(uv(0.0, 0.0, -1.0).0, 0.75);
+        assert_close(uv(0.0, 0.0, -1.0).1, 0.5);
+        assert_close(uv(1.0, 0.0, 0.0).1, 1.0);
+        assert_close(uv(-1.0, 0.0, 0.0).1, 0.0);
+    }
+
+    #[test]
+    fn test_oriented_sphere_rejects_zero_pole() {
+        let zero = Vector3::new(0.0, 0.0, 0.0);
+        assert!(matches!(
+            Geometry::oriented_sphere(zero, 1.0, zero, 0.0),
+            Err(ConstructSphereError::ZeroPole(_))
+        ));
+    }
+
+    #[test]
+    fn test_mesh_triangle_matches_triangle() {
+        let corners = [
+            Vector3::new(-1.0, 0.0, -2.0),
+            Vector3::new(2.0, 0.5, -2.0),
+            Vector3::new(0.0, 3.0, -1.0),
+        ];
+        let [q, b, c] = corners;
+        let triangle = Geometry::triangle(q, b - q, c - q);
+
+        let uvs = [(0.0, 0.0), (1.0, 0.0), (0.5, 1.0)];
+        let mesh_triangle = single_mesh_triangle(corners, None, Some(uvs));
+
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+        let ray = Ray::new(Vector3::new(0.3, 1.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+        let expected = triangle.hit(&ray, &ray_t).unwrap();
+        let (actual, _) = mesh_triangle.hit(&ray, &ray_t).unwrap();
+
+        assert_close(actual.t, expected.t);
+        assert_eq!(actual.face_normal, expected.face_normal);
+        // barycentric (alpha, beta) picks out the mesh's uvs
+        assert_close(actual.alpha, expected.alpha + 0.5 * expected.beta);
+        assert_close(actual.beta, expected.beta);
+
+        let miss = Ray::new(Vector3::new(5.0, 1.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+        assert!(mesh_triangle.hit(&miss, &ray_t).is_none());
+    }
+
+    #[test]
+    fn test_smooth_triangle_interpolates_normals() {
+        let q = Vector3::new(0.0, 0.0, 0.0);
+        let u = Vector3::new(1.0, 0.0, 0.0);
+        let v = Vector3::new(0.0, 1.0, 0.0);
+        let normals = [
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(1.0, 0.0, 1.0),
+            Vector3::new(0.0, 1.0, 1.0),
+        ];
+        let triangle = Geometry::smooth_triangle(q, u, v, normals);
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+
+        // at a corner, the shading normal is that corner's
+        let corner = Ray::new(Vector3::new(1.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = triangle.hit(&corner, &ray_t).unwrap();
+        assert_eq!(hit.face_normal, Vector3::new(0.0, 0.0, 1.0));
+        assert_close(hit.shading_normal.x, normals[1].to_unit().x);
+        assert_close(hit.shading_normal.z, normals[1].to_unit().z);
+
+        // from behind, both flip to face the ray
+        let behind = Ray::new(Vector3::new(0.25, 0.25, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = triangle.hit(&behind, &ray_t).unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(hit.face_normal, Vector3::new(0.0, 0.0, -1.0));
+        assert!(hit.shading_normal.x < 0.0 && hit.shading_normal.z < 0.0);
+        assert_close(hit.shading_normal.length(), 1.0);
+
+        // meshes with normals shade the same way
+        let mesh_triangle = single_mesh_triangle([q, q + u, q + v], Some(normals), None);
+        let expected = triangle.hit(&behind, &ray_t).unwrap();
+        let (actual, _) = mesh_triangle.hit(&behind, &ray_t).unwrap();
+        assert_close(actual.shading_normal.x, expected.shading_normal.x);
+        assert_close(actual.shading_normal.y, expected.shading_normal.y);
+        assert_close(actual.shading_normal.z, expected.shading_normal.z);
+    }
+
+    #[test]
+    fn test_shading_normal_stays_on_the_side_that_was_hit() {
+        let q = Vector3::new(0.0, 0.0, 0.0);
+        let u = Vector3::new(1.0, 0.0, 0.0);
+        let v = Vector3::new(0.0, 1.0, 0.0);
+        // leaning so far out that they interpolate to below the surface near the middle
+        let normals = [
+            Vector3::new(-1.0, -1.0, 0.1),
+            Vector3::new(1.0, -0.5, -0.2),
+            Vector3::new(-0.5, 1.0, -0.2),
+        ];
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+
+        let triangle = Surface::new(Geometry::smooth_triangle(q, u, v, normals), gray());
+        let mesh_triangle = single_mesh_triangle([q, q + u, q + v], Some(normals), None);
+
+        for triangle in [&triangle as &dyn Hittable, &mesh_triangle] {
+            for z in [1.0, -1.0] {
+                let ray = Ray::new(Vector3::new(0.3, 0.3, z), Vector3::new(0.0, 0.0, -z));
+                let (hit, _) = triangle.hit(&ray, &ray_t).unwrap();
+                assert!(dot(hit.shading_normal, hit.face_normal) > 0.0);
+                assert!(dot(hit.shading_normal, ray.direction) < 0.0);
+                assert_close(hit.shading_normal.length(), 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_normals_leave_triangles_flat() {
+        let q = Vector3::new(0.0, 0.0, 0.0);
+        let u = Vector3::new(1.0, 0.0, 0.0);
+        let v = Vector3::new(0.0, 1.0, 0.0);
+        let up = Vector3::new(0.0, 0.0, 1.0);
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+        // where it's hit from below, the first cancels out, the second is missing a corner
+        let ray = Ray::new(Vector3::new(0.25, 0.25, -1.0), up);
+        let opposed = [up, -up, -up];
+        let degenerate = [Vector3::ZERO, Vector3::new(1.0, 0.0, 1.0), up];
+        for normals in [opposed, degenerate] {
+            let triangle = Surface::new(Geometry::smooth_triangle(q, u, v, normals), gray());
+            let mesh_triangle = single_mesh_triangle([q, q + u, q + v], Some(normals), None);
+
+            for triangle in [&triangle as &dyn Hittable, &mesh_triangle] {
+                let (hit, _) = triangle.hit(&ray, &ray_t).unwrap();
+                assert_eq!(hit.shading_normal, hit.face_normal);
+                assert_eq!(hit.face_normal, -up);
+            }
+        }
+    }
+}
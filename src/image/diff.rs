@@ -0,0 +1,167 @@
+//! Metrics for comparing two images of the same dimensions.
+
+use super::Image;
+use crate::{color::luminance, vector::Vector3};
+
+fn assert_same_dimensions(a: &Image, b: &Image) {
+    assert!(
+        a.width() == b.width() && a.height() == b.height(),
+        "can't compare a {}x{} image with a {}x{} image",
+        a.width(),
+        a.height(),
+        b.width(),
+        b.height()
+    );
+}
+
+/// Mean squared error over every channel of every pixel.
+pub fn mse(a: &Image, b: &Image) -> f64 {
+    assert_same_dimensions(a, b);
+
+    let sum = a
+        .pixels()
+        .iter()
+        .zip(b.pixels())
+        .map(|(&a, &b)| (a - b).length_squared())
+        .sum::<f64>();
+
+    sum / (3 * a.pixels().len()).max(1) as f64
+}
+
+/// Peak signal-to-noise ratio in decibels, for images whose values range up to `peak`.
+/// Identical images are infinitely far above the noise floor.
+pub fn psnr(a: &Image, b: &Image, peak: f64) -> f64 {
+    10.0 * (peak.powi(2) / mse(a, b)).log10()
+}
+
+/// Mean structural similarity of the images' luminance, over every 7x7 window.
+///
+/// Stabilizing constants assume values range over [0, 1]; images that don't fit in a
+/// single window are compared as one window, and empty images are identical.
+pub fn ssim(a: &Image, b: &Image) -> f64 {
+    assert_same_dimensions(a, b);
+    // nothing to differ, and no window to average over
+    if a.pixels().is_empty() {
+        return 1.0;
+    }
+
+    const WINDOW: usize = 7;
+    const C1: f64 = 0.01 * 0.01;
+    const C2: f64 = 0.03 * 0.03;
+
+    let width = a.width() as usize;
+    let height = a.height() as usize;
+    let window_width = WINDOW.min(width);
+    let window_height = WINDOW.min(height);
+
+    let luma = |image: &Image| {
+        image
+            .pixels()
+            .iter()
+            .map(|&p| luminance(p))
+            .collect::<Vec<_>>()
+    };
+    let (la, lb) = (luma(a), luma(b));
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for top in 0..=(height - window_height) {
+        for left in 0..=(width - window_width) {
+            let indices = (top..top + window_height)
+                .flat_map(|row| (left..left + window_width).map(move |col| row * width + col));
+
+            let n = (window_width * window_height) as f64;
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) =
+                (0.0, 0.0, 0.0, 0.0, 0.0);
+            for i in indices {
+                sum_a += la[i];
+                sum_b += lb[i];
+                sum_aa += la[i] * la[i];
+                sum_bb += lb[i] * lb[i];
+                sum_ab += la[i] * lb[i];
+            }
+
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let var_a = sum_aa / n - mean_a * mean_a;
+            let var_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a.powi(2) + mean_b.powi(2) + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+
+    total / windows as f64
+}
+
+/// Per-channel absolute difference, e.g. for visualizing where two renders diverge.
+pub fn difference(a: &Image, b: &Image) -> Image {
+    assert_same_dimensions(a, b);
+
+    let mut out = Image::new(a.width(), a.height());
+    for ((out, &a), &b) in out.pixels_mut().iter_mut().zip(a.pixels()).zip(b.pixels()) {
+        let d = a - b;
+        *out = Vector3::new(d.x.abs(), d.y.abs(), d.z.abs());
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(width: u32, height: u32, seed: u64) -> Image {
+        let mut image = Image::new(width, height);
+        let mut state = seed;
+        for pixel in image.pixels_mut() {
+            // a small LCG is plenty for test patterns
+            let mut next = || {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1);
+                (state >> 11) as f64 / (1u64 << 53) as f64
+            };
+            *pixel = Vector3::new(next(), next(), next());
+        }
+        image
+    }
+
+    #[test]
+    fn test_psnr() {
+        let a = noise(10, 8, 1);
+        assert_eq!(psnr(&a, &a, 1.0), f64::INFINITY);
+
+        // every channel off by 0.1 is an MSE of 0.01, i.e. 20dB below a peak of 1
+        let mut b = a.clone();
+        for pixel in b.pixels_mut() {
+            *pixel += Vector3::new(0.1, -0.1, 0.1);
+        }
+        assert!((mse(&a, &b) - 0.01).abs() < 1e-12);
+        assert!((psnr(&a, &b, 1.0) - 20.0).abs() < 1e-9);
+        assert!((psnr(&a, &b, 10.0) - 40.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ssim() {
+        let a = noise(20, 12, 1);
+        assert!((ssim(&a, &a) - 1.0).abs() < 1e-12);
+
+        let mut brighter = a.clone();
+        for pixel in brighter.pixels_mut() {
+            *pixel = *pixel * 0.9 + Vector3::new(0.1, 0.1, 0.1);
+        }
+        let unrelated = noise(20, 12, 2);
+        let near = ssim(&a, &brighter);
+        let far = ssim(&a, &unrelated);
+        assert!(near < 1.0 && far < near && far < 0.2, "{near} {far}");
+        assert_eq!(ssim(&a, &unrelated), ssim(&unrelated, &a));
+
+        // smaller than a window in one direction, and empty
+        let (thin_a, thin_b) = (noise(30, 3, 3), noise(30, 3, 4));
+        assert!(ssim(&thin_a, &thin_b).is_finite());
+        assert_eq!(ssim(&Image::new(0, 0), &Image::new(0, 0)), 1.0);
+        assert_eq!(ssim(&Image::new(5, 0), &Image::new(5, 0)), 1.0);
+    }
+}
@@ -0,0 +1,387 @@
+use std::io::{self, Write};
+
+use super::{Image, zlib};
+use crate::vector::Vector3;
+
+/// Storage for each channel sample.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PixelType {
+    /// IEEE 754 binary16
+    Half,
+    /// IEEE 754 binary32
+    Float,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compression {
+    None,
+    /// zlib over blocks of 16 scanlines
+    Zip,
+}
+
+impl PixelType {
+    fn id(self) -> i32 {
+        match self {
+            PixelType::Half => 1,
+            PixelType::Float => 2,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PixelType::Half => 2,
+            PixelType::Float => 4,
+        }
+    }
+}
+
+impl Compression {
+    fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Zip => 3,
+        }
+    }
+
+    fn scanlines_per_chunk(self) -> usize {
+        match self {
+            Compression::None => 1,
+            Compression::Zip => 16,
+        }
+    }
+}
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+/// format version 2, single-part scanline file
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+
+/// Writes `image` as a single-part scanline OpenEXR file with R, G and B channels.
+pub fn write(
+    writer: &mut impl Write,
+    image: &Image,
+    pixel_type: PixelType,
+    compression: Compression,
+) -> io::Result<()> {
+    let header = header(image, pixel_type, compression);
+
+    let chunks = image
+        .rows()
+        .collect::<Vec<_>>()
+        .chunks(compression.scanlines_per_chunk())
+        .map(|rows| {
+            let mut block = Vec::with_capacity(rows.len() * rows[0].len() * 3 * pixel_type.size());
+            for row in rows {
+                // channels are stored in alphabetical order, one after another per scanline
+                let channels: [fn(&Vector3) -> f64; 3] = [|c| c.z, |c| c.y, |c| c.x];
+                for channel in channels {
+                    for color in row.iter() {
+                        let value = channel(color) as f32;
+                        match pixel_type {
+                            PixelType::Half => {
+                                block.extend_from_slice(&f32_to_f16(value).to_le_bytes())
+                            }
+                            PixelType::Float => block.extend_from_slice(&value.to_le_bytes()),
+                        }
+                    }
+                }
+            }
+
+            match compression {
+                Compression::None => block,
+                Compression::Zip => zip_block(block),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION)?;
+    writer.write_all(&header)?;
+
+    // the offset table points at each chunk's absolute position in the file
+    let table_len = chunks.len() * size_of::<u64>();
+    let mut offset = (MAGIC.len() + VERSION.len() + header.len() + table_len) as u64;
+    for chunk in &chunks {
+        writer.write_all(&offset.to_le_bytes())?;
+        offset += (2 * size_of::<i32>() + chunk.len()) as u64;
+    }
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        let y = (i * compression.scanlines_per_chunk()) as i32;
+        writer.write_all(&y.to_le_bytes())?;
+        writer.write_all(&(chunk.len() as i32).to_le_bytes())?;
+        writer.write_all(chunk)?;
+    }
+
+    Ok(())
+}
+
+fn header(image: &Image, pixel_type: PixelType, compression: Compression) -> Vec<u8> {
+    let mut header = Vec::new();
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+
+    let mut channels = Vec::new();
+    for name in *b"BGR" {
+        channels.extend_from_slice(&[name, 0]);
+        channels.extend_from_slice(&pixel_type.id().to_le_bytes());
+        // pLinear and reserved bytes, then x and y sampling
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+
+    let window = [0i32, 0, image.width() as i32 - 1, image.height() as i32 - 1]
+        .map(i32::to_le_bytes)
+        .concat();
+
+    attribute("channels", "chlist", &channels);
+    attribute("compression", "compression", &[compression.id()]);
+    attribute("dataWindow", "box2i", &window);
+    attribute("displayWindow", "box2i", &window);
+    // increasing y
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0u8; 8]);
+    attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+
+    header.push(0);
+    header
+}
+
+/// Deinterleaves and delta-encodes the block before deflating it, as OpenEXR's ZIP
+/// compression expects. Blocks that don't shrink are stored as-is.
+fn zip_block(block: Vec<u8>) -> Vec<u8> {
+    let half = block.len().div_ceil(2);
+    let mut predicted = vec![0u8; block.len()];
+    for (i, &byte) in block.iter().enumerate() {
+        predicted[if i % 2 == 0 { i / 2 } else { half + i / 2 }] = byte;
+    }
+
+    let mut previous = predicted[0];
+    for byte in predicted.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+
+    // EXR stores the raw deflate payload inside a zlib stream, header and checksum included
+    let compressed = zlib::compress(&predicted);
+    if compressed.len() < block.len() {
+        compressed
+    } else {
+        block
+    }
+}
+
+/// Rounds to the nearest binary16, ties to even; out-of-range values become infinity.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // infinity or NaN, keeping NaNs quiet
+        return sign | 0x7c00 | if mantissa != 0 { 0x0200 } else { 0 };
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            // rounds to zero
+            return sign;
+        }
+
+        // subnormal: shift the mantissa (with its implicit leading one) into place
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let rounded = round_shift(mantissa, shift);
+        return sign | rounded as u16;
+    }
+
+    // a carry out of the mantissa correctly bumps the exponent (possibly to infinity)
+    let combined = (half_exponent as u32) << 23 | mantissa;
+    sign | round_shift(combined, 13) as u16
+}
+
+fn round_shift(value: u32, shift: u32) -> u32 {
+    let truncated = value >> shift;
+    let remainder = value & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+
+    if remainder > halfway || (remainder == halfway && truncated & 1 == 1) {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take<'a>(bytes: &mut &'a [u8], n: usize) -> &'a [u8] {
+        let (taken, rest) = bytes.split_at(n);
+        *bytes = rest;
+        taken
+    }
+
+    fn take_i32(bytes: &mut &[u8]) -> i32 {
+        i32::from_le_bytes(take(bytes, 4).try_into().unwrap())
+    }
+
+    fn take_str(bytes: &mut &[u8]) -> String {
+        let len = bytes.iter().position(|&byte| byte == 0).unwrap();
+        let name = String::from_utf8(take(bytes, len).to_vec()).unwrap();
+        take(bytes, 1);
+        name
+    }
+
+    /// Undoes `zip_block`'s delta encoding and deinterleaving.
+    fn unpredict(mut predicted: Vec<u8>) -> Vec<u8> {
+        for i in 1..predicted.len() {
+            predicted[i] = predicted[i - 1]
+                .wrapping_add(predicted[i])
+                .wrapping_sub(128);
+        }
+
+        let half = predicted.len().div_ceil(2);
+        (0..predicted.len())
+            .map(|i| predicted[if i % 2 == 0 { i / 2 } else { half + i / 2 }])
+            .collect()
+    }
+
+    #[test]
+    fn test_file_structure() {
+        let (width, height) = (40, 20);
+        let mut image = Image::new(width, height);
+        for (i, pixel) in image.pixels_mut().iter_mut().enumerate() {
+            let (x, y) = ((i % 40) as f64, (i / 40) as f64);
+            *pixel = Vector3::new(x / 8.0, y * 100.0, -0.25);
+        }
+
+        for (pixel_type, compression) in [
+            (PixelType::Half, Compression::Zip),
+            (PixelType::Float, Compression::Zip),
+            (PixelType::Float, Compression::None),
+        ] {
+            let mut file = Vec::new();
+            write(&mut file, &image, pixel_type, compression).unwrap();
+            let mut bytes = file.as_slice();
+
+            assert_eq!(take(&mut bytes, 4), MAGIC);
+            assert_eq!(take(&mut bytes, 4), VERSION);
+
+            let mut attributes = Vec::new();
+            while bytes[0] != 0 {
+                let name = take_str(&mut bytes);
+                let kind = take_str(&mut bytes);
+                let len = take_i32(&mut bytes) as usize;
+                attributes.push((name, kind, take(&mut bytes, len).to_vec()));
+            }
+            take(&mut bytes, 1);
+
+            let names = attributes
+                .iter()
+                .map(|(name, kind, _)| format!("{name}: {kind}"))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                names,
+                [
+                    "channels: chlist",
+                    "compression: compression",
+                    "dataWindow: box2i",
+                    "displayWindow: box2i",
+                    "lineOrder: lineOrder",
+                    "pixelAspectRatio: float",
+                    "screenWindowCenter: v2f",
+                    "screenWindowWidth: float",
+                ]
+            );
+
+            let mut channels = attributes[0].2.as_slice();
+            for name in ["B", "G", "R"] {
+                assert_eq!(take_str(&mut channels), name);
+                assert_eq!(take_i32(&mut channels), pixel_type.id());
+                assert_eq!(take(&mut channels, 4), [0; 4]);
+                assert_eq!((take_i32(&mut channels), take_i32(&mut channels)), (1, 1));
+            }
+            assert_eq!(channels, [0]);
+            assert_eq!(attributes[1].2, [compression.id()]);
+            let window = [0i32, 0, 39, 19].map(i32::to_le_bytes).concat();
+            assert_eq!((&attributes[2].2, &attributes[3].2), (&window, &window));
+
+            let lines = compression.scanlines_per_chunk();
+            let n_chunks = (height as usize).div_ceil(lines);
+            let offsets = (0..n_chunks)
+                .map(|_| u64::from_le_bytes(take(&mut bytes, 8).try_into().unwrap()))
+                .collect::<Vec<_>>();
+
+            for (i, rows) in image.rows().collect::<Vec<_>>().chunks(lines).enumerate() {
+                let mut chunk = &file[offsets[i] as usize..];
+                assert_eq!(take_i32(&mut chunk), (i * lines) as i32);
+                let len = take_i32(&mut chunk) as usize;
+                let data = take(&mut chunk, len);
+
+                let mut expected = Vec::new();
+                for row in rows {
+                    let channels: [fn(&Vector3) -> f64; 3] = [|c| c.z, |c| c.y, |c| c.x];
+                    for channel in channels {
+                        for color in row.iter() {
+                            let value = channel(color) as f32;
+                            match pixel_type {
+                                PixelType::Half => expected.extend(f32_to_f16(value).to_le_bytes()),
+                                PixelType::Float => expected.extend(value.to_le_bytes()),
+                            }
+                        }
+                    }
+                }
+
+                let actual = match compression {
+                    Compression::None => data.to_vec(),
+                    Compression::Zip => {
+                        // smooth enough that every block should shrink
+                        assert!(len < expected.len());
+                        unpredict(zlib::decompress(data, expected.len()).unwrap())
+                    }
+                };
+                assert_eq!(actual, expected);
+            }
+
+            // chunks follow the table, back to back
+            let last = *offsets.last().unwrap() as usize;
+            let last_len = i32::from_le_bytes(file[last + 4..last + 8].try_into().unwrap());
+            assert_eq!(last + 8 + last_len as usize, file.len());
+        }
+    }
+
+    #[test]
+    fn test_f32_to_f16() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7e00, 0x7e00);
+        // smallest subnormal and the largest value that rounds down to zero
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(2f32.powi(-25)), 0x0000);
+        // 1 + 2^-11 is halfway between 1 and the next half; ties go to even
+        assert_eq!(f32_to_f16(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+    }
+}
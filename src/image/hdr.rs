@@ -0,0 +1,220 @@
+use std::io::{self, Write};
+
+use super::Image;
+use crate::vector::Vector3;
+
+/// Writes `image` as a Radiance RGBE (.hdr) file, run-length encoding scanlines where the
+/// format allows it.
+pub fn write(writer: &mut impl Write, image: &Image) -> io::Result<()> {
+    writeln!(writer, "#?RADIANCE")?;
+    writeln!(writer, "FORMAT=32-bit_rle_rgbe")?;
+    writeln!(writer)?;
+    writeln!(writer, "-Y {} +X {}", image.height(), image.width())?;
+
+    let width = image.width() as usize;
+    let mut scanline = vec![[0u8; 4]; width];
+    let mut encoded = Vec::new();
+
+    for row in image.rows() {
+        for (rgbe, &color) in scanline.iter_mut().zip(row) {
+            *rgbe = to_rgbe(color);
+        }
+
+        // the run-length encoding can only describe scanlines in this range
+        if !(8..0x8000).contains(&width) {
+            writer.write_all(scanline.as_flattened())?;
+            continue;
+        }
+
+        encoded.clear();
+        encoded.extend_from_slice(&[2, 2, (width >> 8) as u8, width as u8]);
+        for component in 0..4 {
+            let values = scanline
+                .iter()
+                .map(|rgbe| rgbe[component])
+                .collect::<Vec<_>>();
+            encode_component(&mut encoded, &values);
+        }
+        writer.write_all(&encoded)?;
+    }
+
+    Ok(())
+}
+
+/// Shared-exponent encoding: each channel keeps 8 bits of mantissa relative to the brightest.
+fn to_rgbe(color: Vector3) -> [u8; 4] {
+    let (r, g, b) = (color.x.max(0.0), color.y.max(0.0), color.z.max(0.0));
+    let brightest = r.max(g).max(b);
+    if brightest < 1e-32 || !brightest.is_finite() {
+        return [0, 0, 0, 0];
+    }
+
+    // brightest = mantissa * 2^exponent, mantissa in [0.5, 1)
+    let mut exponent = brightest.log2().floor() as i32 + 1;
+    if brightest / 2f64.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let scale = 256.0 / 2f64.powi(exponent);
+
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+/// Appends one component of a scanline as a sequence of runs (count > 128, then a value)
+/// and literal dumps (count <= 128, then that many values).
+fn encode_component(out: &mut Vec<u8>, values: &[u8]) {
+    const MIN_RUN: usize = 4;
+    const MAX_LENGTH: usize = 127;
+
+    let run_length = |start: usize| {
+        values[start..]
+            .iter()
+            .take(MAX_LENGTH)
+            .take_while(|&&value| value == values[start])
+            .count()
+    };
+
+    let mut pos = 0;
+    while pos < values.len() {
+        // extend a literal dump until the next run worth encoding
+        let mut dump_end = pos;
+        while dump_end < values.len()
+            && dump_end - pos < MAX_LENGTH
+            && run_length(dump_end) < MIN_RUN
+        {
+            dump_end += 1;
+        }
+
+        if dump_end > pos {
+            out.push((dump_end - pos) as u8);
+            out.extend_from_slice(&values[pos..dump_end]);
+            pos = dump_end;
+            continue;
+        }
+
+        let run = run_length(pos);
+        out.extend_from_slice(&[128 + run as u8, values[pos]]);
+        pos += run;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Undoes `encode_component`, for `len` values.
+    fn decode_component(encoded: &mut &[u8], len: usize) -> Vec<u8> {
+        let mut values = Vec::with_capacity(len);
+        while values.len() < len {
+            let (&count, rest) = encoded.split_first().unwrap();
+            if count > 128 {
+                values.extend(std::iter::repeat_n(rest[0], count as usize - 128));
+                *encoded = &rest[1..];
+            } else {
+                values.extend_from_slice(&rest[..count as usize]);
+                *encoded = &rest[count as usize..];
+            }
+        }
+        assert_eq!(values.len(), len);
+        values
+    }
+
+    fn from_rgbe([r, g, b, e]: [u8; 4]) -> Vector3 {
+        if e == 0 {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+        let scale = 2f64.powi(e as i32 - 136);
+        Vector3::new(
+            (r as f64 + 0.5) * scale,
+            (g as f64 + 0.5) * scale,
+            (b as f64 + 0.5) * scale,
+        )
+    }
+
+    #[test]
+    fn test_encode_component() {
+        let encode = |values: &[u8]| {
+            let mut out = Vec::new();
+            encode_component(&mut out, values);
+            out
+        };
+
+        // runs shorter than four aren't worth it, and stay in the dump
+        assert_eq!(encode(&[1, 2, 2, 2, 3]), [5, 1, 2, 2, 2, 3]);
+        assert_eq!(encode(&[1, 2, 2, 2, 2, 3]), [1, 1, 128 + 4, 2, 1, 3]);
+
+        // runs and dumps are both split at 127
+        let mut values = vec![7; 130];
+        values.extend([1, 2, 3]);
+        assert_eq!(encode(&values), [128 + 127, 7, 6, 7, 7, 7, 1, 2, 3]);
+
+        let values = (0..200).map(|i| (i % 2) as u8).collect::<Vec<_>>();
+        let encoded = encode(&values);
+        assert_eq!(encoded.len(), 2 + values.len());
+        assert_eq!((encoded[0], encoded[128]), (127, 73));
+        assert_eq!(decode_component(&mut encoded.as_slice(), 200), values);
+
+        let values = [vec![5; 128], vec![9; 129], (0..130).collect()].concat();
+        assert_eq!(
+            decode_component(&mut encode(&values).as_slice(), values.len()),
+            values
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (width, height) = (200, 3);
+        let mut image = Image::new(width, height);
+        for (i, pixel) in image.pixels_mut().iter_mut().enumerate() {
+            // long flat stretches with some noisy ones, over a wide range of exponents
+            let x = if i % width as usize > 120 { i } else { i / 50 } as f64;
+            *pixel = Vector3::new(x * 0.37, 1e-3 * x, 1e3 / (x + 1.0));
+        }
+        image.pixels_mut()[0] = Vector3::new(0.0, 0.0, 0.0);
+        image.pixels_mut()[1] = Vector3::new(-1.0, 0.5, 0.0);
+
+        let mut bytes = Vec::new();
+        write(&mut bytes, &image).unwrap();
+
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 3 +X 200\n";
+        assert_eq!(&bytes[..header.len()], header);
+
+        let mut encoded = &bytes[header.len()..];
+        for row in image.rows() {
+            let (start, rest) = encoded.split_at(4);
+            assert_eq!(start, [2, 2, 0, 200]);
+            encoded = rest;
+
+            let components: [Vec<u8>; 4] =
+                std::array::from_fn(|_| decode_component(&mut encoded, width as usize));
+            for (x, &expected) in row.iter().enumerate() {
+                let decoded = from_rgbe(components.each_ref().map(|component| component[x]));
+                let expected = Vector3::new(expected.x.max(0.0), expected.y, expected.z);
+                // 8 bits of mantissa, relative to the brightest channel
+                let tolerance = expected.max_component() / 128.0;
+                assert!(
+                    (decoded - expected).length() <= tolerance,
+                    "{expected:?} -> {decoded:?}"
+                );
+            }
+        }
+        assert!(encoded.is_empty());
+    }
+
+    #[test]
+    fn test_narrow_scanlines_are_flat() {
+        let mut image = Image::new(4, 2);
+        image.pixels_mut().fill(Vector3::new(1.0, 0.5, 0.25));
+
+        let mut bytes = Vec::new();
+        write(&mut bytes, &image).unwrap();
+
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 4\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes[header.len()..], [128, 64, 32, 129].repeat(8));
+    }
+}
@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::ops::{Index, IndexMut};
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::{color::DisplayTransform, vector::Vector3};
+
+pub mod diff;
+pub mod exr;
+pub mod hdr;
+pub mod png;
+pub mod ppm;
+mod zlib;
+
+/// File formats an [`Image`] can be written as.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ImageFormat {
+    /// Plain-text portable pixmap (P3)
+    PlainPpm,
+    /// Binary portable pixmap (P6)
+    Ppm,
+    /// Portable Float Map, unclamped linear radiance
+    Pfm,
+    /// 8-bit RGB PNG
+    Png,
+    /// Radiance RGBE, unclamped linear radiance
+    Hdr,
+    /// Scanline OpenEXR, unclamped linear radiance
+    Exr(exr::PixelType, exr::Compression),
+}
+
+impl ImageFormat {
+    /// Guesses the format from a path's file extension, ignoring case.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "pfm" => Some(ImageFormat::Pfm),
+            "png" => Some(ImageFormat::Png),
+            "hdr" => Some(ImageFormat::Hdr),
+            "exr" => Some(ImageFormat::Exr(
+                exr::PixelType::Half,
+                exr::Compression::Zip,
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SaveImageError {
+    #[error(
+        "can't infer an image format from {0:?} (expected one of .ppm, .png, .pfm, .hdr or .exr)"
+    )]
+    UnknownFormat(Box<Path>),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum ReadImageError {
+    #[error("can't infer a readable image format from {0:?} (expected .ppm or .png)")]
+    UnknownFormat(Box<Path>),
+    #[error("malformed image: {0}")]
+    Malformed(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A framebuffer of linear RGB radiance, stored row by row starting from the top-left pixel.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Box<[Vector3]>,
+}
+
+impl Image {
+    /// A black image of the given dimensions.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vector3::ZERO; width as usize * height as usize].into_boxed_slice(),
+        }
+    }
+
+    /// An image of the given dimensions from its pixels, row by row.
+    fn from_pixels(width: u32, height: u32, pixels: Vec<Vector3>) -> Self {
+        debug_assert_eq!(pixels.len(), width as usize * height as usize);
+        Self {
+            width,
+            height,
+            pixels: pixels.into_boxed_slice(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Vector3] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Vector3] {
+        &mut self.pixels
+    }
+
+    /// Rows of pixels, top to bottom.
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &[Vector3]> {
+        self.pixels.chunks_exact(self.width.max(1) as usize)
+    }
+
+    /// Writes the image in `format`. `display` is only used by the formats that can't hold
+    /// linear radiance directly.
+    pub fn write(
+        &self,
+        writer: &mut impl Write,
+        format: ImageFormat,
+        display: &DisplayTransform,
+    ) -> io::Result<()> {
+        display
+            .tone_mapper
+            .validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        display
+            .transfer
+            .validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        match format {
+            ImageFormat::PlainPpm => ppm::write(writer, self, display),
+            ImageFormat::Ppm => ppm::write_binary(writer, self, display),
+            ImageFormat::Pfm => ppm::write_float(writer, self),
+            ImageFormat::Png => png::write(writer, self, display),
+            ImageFormat::Hdr => hdr::write(writer, self),
+            ImageFormat::Exr(pixel_type, compression) => {
+                exr::write(writer, self, pixel_type, compression)
+            }
+        }
+    }
+
+    /// Writes the image to `path`, in the format implied by its extension.
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+        display: &DisplayTransform,
+    ) -> Result<(), SaveImageError> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path)
+            .ok_or_else(|| SaveImageError::UnknownFormat(path.into()))?;
+
+        Ok(self.save_as(path, format, display)?)
+    }
+
+    /// Writes the image to `path` in the given format, whatever its extension.
+    pub fn save_as(
+        &self,
+        path: impl AsRef<Path>,
+        format: ImageFormat,
+        display: &DisplayTransform,
+    ) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, format, display)?;
+        writer.flush()
+    }
+
+    /// Reads the image at `path`, in the format implied by its extension. Only PPM and PNG
+    /// files can be read, and their samples come back as stored, without linearizing.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ReadImageError> {
+        let path = path.as_ref();
+        match ImageFormat::from_path(path) {
+            Some(ImageFormat::Ppm) => ppm::read(&mut File::open(path).map(BufReader::new)?),
+            Some(ImageFormat::Png) => png::read(&mut File::open(path).map(BufReader::new)?),
+            _ => Err(ReadImageError::UnknownFormat(path.into())),
+        }
+    }
+
+    fn offset(&self, col: u32, row: u32) -> usize {
+        assert!(
+            col < self.width && row < self.height,
+            "pixel ({col}, {row}) out of bounds for {}x{} image",
+            self.width,
+            self.height
+        );
+
+        row as usize * self.width as usize + col as usize
+    }
+}
+
+/// Indexed by (column, row).
+impl Index<(u32, u32)> for Image {
+    type Output = Vector3;
+
+    fn index(&self, (col, row): (u32, u32)) -> &Vector3 {
+        &self.pixels[self.offset(col, row)]
+    }
+}
+
+impl IndexMut<(u32, u32)> for Image {
+    fn index_mut(&mut self, (col, row): (u32, u32)) -> &mut Vector3 {
+        let offset = self.offset(col, row);
+        &mut self.pixels[offset]
+    }
+}
@@ -0,0 +1,648 @@
+use std::io::{self, Read, Write};
+
+use super::{Image, ReadImageError, zlib};
+use crate::{
+    color::{DisplayTransform, TransferFunction},
+    vector::Vector3,
+};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Largest IDAT payload we emit; decoders accept anything up to 2^31 - 1,
+/// but smaller chunks let streaming decoders check and consume each one as it arrives.
+const MAX_IDAT_LEN: usize = 1 << 20;
+
+/// Writes `image` as an 8-bit RGB PNG, tagged with the display transform's encoding.
+pub fn write(writer: &mut impl Write, image: &Image, display: &DisplayTransform) -> io::Result<()> {
+    writer.write_all(&SIGNATURE)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&image.width().to_be_bytes());
+    header.extend_from_slice(&image.height().to_be_bytes());
+    // bit depth 8, color type 2 (truecolor), default compression/filter, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(writer, b"IHDR", &header)?;
+
+    match display.transfer {
+        // perceptual rendering intent
+        TransferFunction::Srgb => write_chunk(writer, b"sRGB", &[0])?,
+        TransferFunction::Gamma(gamma) => {
+            write_chunk(writer, b"gAMA", &gama(1.0 / gamma).to_be_bytes())?
+        }
+        TransferFunction::Linear => write_chunk(writer, b"gAMA", &gama(1.0).to_be_bytes())?,
+    }
+
+    let compressed = zlib::compress(&filtered_scanlines(image, display));
+    for data in compressed.chunks(MAX_IDAT_LEN) {
+        write_chunk(writer, b"IDAT", data)?;
+    }
+
+    write_chunk(writer, b"IEND", &[])
+}
+
+/// Reads a PNG of any bit depth and color type, interlaced or not.
+///
+/// Samples are scaled to [0, 1] by the bit depth but otherwise left in whatever encoding the
+/// file uses; nothing is linearized, whatever its sRGB or gAMA chunks say. Alpha is dropped.
+pub fn read(reader: &mut impl Read) -> Result<Image, ReadImageError> {
+    let mut signature = [0u8; 8];
+    reader.read_exact(&mut signature)?;
+    if signature != SIGNATURE {
+        return Err(malformed("missing PNG signature"));
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut compressed = Vec::new();
+    loop {
+        let (kind, data) = read_chunk(reader)?;
+        match &kind {
+            b"IHDR" => header = Some(Header::parse(&data)?),
+            b"PLTE" => {
+                palette = data
+                    .as_chunks::<3>()
+                    .0
+                    .iter()
+                    .map(|rgb| {
+                        let [r, g, b] = rgb.map(|c| f64::from(c) / 255.0);
+                        Vector3::new(r, g, b)
+                    })
+                    .collect()
+            }
+            b"IDAT" => compressed.extend_from_slice(&data),
+            b"IEND" => break,
+            // bit 5 of the first byte marks ancillary chunks, which are safe to skip
+            _ if kind[0] & 0x20 == 0 => {
+                return Err(malformed(format!(
+                    "unsupported critical chunk {:?}",
+                    String::from_utf8_lossy(&kind)
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or_else(|| malformed("missing IHDR chunk"))?;
+    if header.color_type == 3 && palette.is_empty() {
+        return Err(malformed("indexed-color PNG without a PLTE chunk"));
+    }
+
+    // the header says exactly how much data there should be, so nothing sized by it is
+    // allocated until the compressed stream has actually delivered that much
+    let len = header.data_len().ok_or_else(|| {
+        malformed(format!(
+            "{}x{} image is too large",
+            header.width, header.height
+        ))
+    })?;
+    let data = zlib::decompress(&compressed, len)?;
+    if data.len() < len {
+        return Err(malformed("image data ends early"));
+    }
+
+    let mut image = Image::new(header.width, header.height);
+    let mut remaining = data.as_slice();
+    for pass in header.passes() {
+        // can't overflow, or data_len wouldn't have
+        let stride = header.stride(pass.width).unwrap();
+        let (scanlines, rest) = remaining.split_at((stride + 1) * pass.height as usize);
+        remaining = rest;
+
+        let mut prior = vec![0u8; stride];
+        let mut current = vec![0u8; stride];
+        for (y, scanline) in scanlines.chunks_exact(stride + 1).enumerate() {
+            current.copy_from_slice(&scanline[1..]);
+            unfilter(scanline[0], &mut current, &prior, header.filter_bpp())?;
+
+            for x in 0..pass.width {
+                let col = pass.x0 + x * pass.dx;
+                let row = pass.y0 + y as u32 * pass.dy;
+                image[(col, row)] = header.pixel(&current, x, &palette)?;
+            }
+            std::mem::swap(&mut prior, &mut current);
+        }
+    }
+
+    Ok(image)
+}
+
+fn malformed(message: impl Into<String>) -> ReadImageError {
+    ReadImageError::Malformed(message.into())
+}
+
+fn read_chunk(reader: &mut impl Read) -> Result<([u8; 4], Vec<u8>), ReadImageError> {
+    let mut prefix = [0u8; 8];
+    reader.read_exact(&mut prefix)?;
+    let len = u32::from_be_bytes(prefix[..4].try_into().unwrap());
+    let kind: [u8; 4] = prefix[4..].try_into().unwrap();
+
+    let mut data = Vec::new();
+    reader.take(u64::from(len)).read_to_end(&mut data)?;
+    let mut crc = [0u8; 4];
+    reader.read_exact(&mut crc)?;
+    if data.len() != len as usize {
+        return Err(malformed("truncated chunk"));
+    }
+    if u32::from_be_bytes(crc) != !crc32_update(crc32_update(!0, &kind), &data) {
+        return Err(malformed(format!(
+            "CRC mismatch in {:?} chunk",
+            String::from_utf8_lossy(&kind)
+        )));
+    }
+
+    Ok((kind, data))
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+/// The pixels of one interlacing pass: every `dx`th column from `x0`, and every `dy`th
+/// row from `y0`.
+struct Pass {
+    x0: u32,
+    y0: u32,
+    dx: u32,
+    dy: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, ReadImageError> {
+        let &[bit_depth, color_type, compression, filter, interlace] = data.get(8..).unwrap_or(&[])
+        else {
+            return Err(malformed("IHDR chunk has the wrong length"));
+        };
+
+        let allowed_depths: &[u8] = match color_type {
+            0 => &[1, 2, 4, 8, 16],
+            3 => &[1, 2, 4, 8],
+            2 | 4 | 6 => &[8, 16],
+            _ => return Err(malformed(format!("invalid color type {color_type}"))),
+        };
+        if !allowed_depths.contains(&bit_depth) {
+            return Err(malformed(format!(
+                "invalid bit depth {bit_depth} for color type {color_type}"
+            )));
+        }
+        if compression != 0 || filter != 0 || interlace > 1 {
+            return Err(malformed("unknown compression, filter or interlace method"));
+        }
+
+        let width = u32::from_be_bytes(data[0..4].try_into().unwrap());
+        let height = u32::from_be_bytes(data[4..8].try_into().unwrap());
+        // the spec limits both to 2^31 - 1, like chunk lengths
+        if !(1..=i32::MAX as u32).contains(&width) || !(1..=i32::MAX as u32).contains(&height) {
+            return Err(malformed(format!("invalid dimensions {width}x{height}")));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced: interlace == 1,
+        })
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * usize::from(self.bit_depth)
+    }
+
+    /// Distance in bytes to the corresponding byte of the previous pixel, rounded up.
+    fn filter_bpp(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+
+    /// Bytes in a scanline `width` pixels wide, not counting its filter type.
+    fn stride(&self, width: u32) -> Option<usize> {
+        Some(
+            (width as usize)
+                .checked_mul(self.bits_per_pixel())?
+                .div_ceil(8),
+        )
+    }
+
+    /// Length of the decompressed image data: every pass's scanlines, each with its filter
+    /// type. `None` if that, or the image itself, wouldn't fit in memory.
+    fn data_len(&self) -> Option<usize> {
+        (self.width as usize)
+            .checked_mul(self.height as usize)?
+            .checked_mul(size_of::<Vector3>())?;
+
+        self.passes().try_fold(0usize, |len, pass| {
+            let scanlines = self
+                .stride(pass.width)?
+                .checked_add(1)?
+                .checked_mul(pass.height as usize)?;
+            len.checked_add(scanlines)
+        })
+    }
+
+    /// Non-empty passes in the order their data is stored, which is just the whole image
+    /// when it isn't interlaced.
+    fn passes(&self) -> impl Iterator<Item = Pass> + use<> {
+        /// (x0, y0, dx, dy) for each Adam7 pass
+        const ADAM7: [(u32, u32, u32, u32); 7] = [
+            (0, 0, 8, 8),
+            (4, 0, 8, 8),
+            (0, 4, 4, 8),
+            (2, 0, 4, 4),
+            (0, 2, 2, 4),
+            (1, 0, 2, 2),
+            (0, 1, 1, 2),
+        ];
+
+        let passes: &[_] = if self.interlaced {
+            &ADAM7
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+        let (width, height) = (self.width, self.height);
+
+        passes
+            .iter()
+            .map(move |&(x0, y0, dx, dy)| Pass {
+                x0,
+                y0,
+                dx,
+                dy,
+                width: width.saturating_sub(x0).div_ceil(dx),
+                height: height.saturating_sub(y0).div_ceil(dy),
+            })
+            .filter(|pass| pass.width > 0 && pass.height > 0)
+    }
+
+    /// The `x`th pixel of an unfiltered scanline.
+    fn pixel(
+        &self,
+        scanline: &[u8],
+        x: u32,
+        palette: &[Vector3],
+    ) -> Result<Vector3, ReadImageError> {
+        let depth = usize::from(self.bit_depth);
+        let sample = |channel: usize| -> u16 {
+            let bit = (x as usize * self.channels() + channel) * depth;
+            match depth {
+                16 => u16::from_be_bytes([scanline[bit / 8], scanline[bit / 8 + 1]]),
+                // sub-byte samples are packed from the most significant bit down
+                _ => {
+                    u16::from(scanline[bit / 8] >> (8 - depth - bit % 8) & ((1 << depth) - 1) as u8)
+                }
+            }
+        };
+        let max = f64::from((1u32 << depth) - 1);
+        let value = |channel: usize| f64::from(sample(channel)) / max;
+
+        Ok(match self.color_type {
+            0 | 4 => Vector3::new(value(0), value(0), value(0)),
+            3 => *palette
+                .get(usize::from(sample(0)))
+                .ok_or_else(|| malformed(format!("palette index {} out of range", sample(0))))?,
+            _ => Vector3::new(value(0), value(1), value(2)),
+        })
+    }
+}
+
+/// Reverses the filter a scanline was stored with, given the already unfiltered row above.
+fn unfilter(
+    filter: u8,
+    current: &mut [u8],
+    prior: &[u8],
+    bpp: usize,
+) -> Result<(), ReadImageError> {
+    for i in 0..current.len() {
+        let a = if i >= bpp { current[i - bpp] } else { 0 };
+        let b = prior[i];
+        let c = if i >= bpp { prior[i - bpp] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((u16::from(a) + u16::from(b)) / 2) as u8,
+            4 => paeth(a, b, c),
+            _ => return Err(malformed(format!("invalid filter type {filter}"))),
+        };
+        current[i] = current[i].wrapping_add(predicted);
+    }
+
+    Ok(())
+}
+
+/// gAMA stores the encoding exponent scaled by 100000.
+fn gama(exponent: f64) -> u32 {
+    (exponent * 100_000.0).round() as u32
+}
+
+fn write_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+
+    let crc = crc32_update(crc32_update(!0, kind), data);
+    writer.write_all(&(!crc).to_be_bytes())
+}
+
+/// Each scanline prefixed by its filter type, choosing per row whichever filter
+/// minimizes the sum of absolute differences (the heuristic recommended by the spec).
+fn filtered_scanlines(image: &Image, display: &DisplayTransform) -> Vec<u8> {
+    const BPP: usize = 3;
+    let stride = image.width() as usize * BPP;
+
+    let mut out = Vec::with_capacity((stride + 1) * image.height() as usize);
+    let mut prior = vec![0u8; stride];
+    let mut current = Vec::with_capacity(stride);
+    let mut candidate = vec![0u8; stride];
+    let mut best = vec![0u8; stride];
+
+    for row in image.rows() {
+        current.clear();
+        current.extend(row.iter().flat_map(|&color| display.to_rgb8(color)));
+
+        let mut best_filter = 0;
+        let mut best_score = u64::MAX;
+        for filter in 0..5u8 {
+            for i in 0..stride {
+                let a = if i >= BPP { current[i - BPP] } else { 0 };
+                let b = prior[i];
+                let c = if i >= BPP { prior[i - BPP] } else { 0 };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((u16::from(a) + u16::from(b)) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                candidate[i] = current[i].wrapping_sub(predicted);
+            }
+
+            let score = candidate
+                .iter()
+                .map(|&byte| u64::from((byte as i8).unsigned_abs()))
+                .sum();
+            if score < best_score {
+                best_score = score;
+                best_filter = filter;
+                best.copy_from_slice(&candidate);
+            }
+        }
+
+        out.push(best_filter);
+        out.extend_from_slice(&best);
+        std::mem::swap(&mut prior, &mut current);
+    }
+
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let pa = (p - i16::from(a)).abs();
+    let pb = (p - i16::from(b)).abs();
+    let pc = (p - i16::from(c)).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+/// Feeds `data` into a running CRC-32; callers start from `!0` and invert the final value.
+fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, &byte| {
+        CRC_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ToneMapper;
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(!crc32_update(!0, b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_iend_chunk() {
+        let mut out = Vec::new();
+        write_chunk(&mut out, b"IEND", &[]).unwrap();
+
+        assert_eq!(
+            out,
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+    }
+
+    #[test]
+    fn test_read_what_we_write() {
+        let mut image = Image::new(7, 3);
+        for (i, pixel) in image.pixels_mut().iter_mut().enumerate() {
+            let i = i as f64;
+            *pixel = Vector3::new(i / 255.0, (3.0 * i) / 255.0, (255.0 - i) / 255.0);
+        }
+        let display = DisplayTransform {
+            tone_mapper: ToneMapper::Clamp,
+            transfer: TransferFunction::Linear,
+        };
+
+        let mut out = Vec::new();
+        write(&mut out, &image, &display).unwrap();
+
+        assert_eq!(read(&mut out.as_slice()).unwrap(), image);
+    }
+
+    /// A PNG with the given header fields and already filtered image data.
+    fn file(
+        width: u32,
+        height: u32,
+        depth_and_type: [u8; 2],
+        interlace: u8,
+        plte: &[u8],
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut header = [width.to_be_bytes(), height.to_be_bytes()].concat();
+        header.extend([depth_and_type[0], depth_and_type[1], 0, 0, interlace]);
+
+        let mut out = SIGNATURE.to_vec();
+        write_chunk(&mut out, b"IHDR", &header).unwrap();
+        if !plte.is_empty() {
+            write_chunk(&mut out, b"PLTE", plte).unwrap();
+        }
+        write_chunk(&mut out, b"IDAT", &zlib::compress(data)).unwrap();
+        write_chunk(&mut out, b"IEND", &[]).unwrap();
+        out
+    }
+
+    fn gray(value: u8) -> Vector3 {
+        let value = f64::from(value) / 255.0;
+        Vector3::new(value, value, value)
+    }
+
+    #[test]
+    fn test_read_interlaced() {
+        let (width, height) = (7u32, 5u32);
+        let value = |x: u32, y: u32| (10 * y + x) as u8;
+
+        // each Adam7 pass's scanlines in turn, unfiltered, skipping passes with no pixels
+        let mut data = Vec::new();
+        for (x0, y0, dx, dy) in [
+            (0, 0, 8, 8),
+            (4, 0, 8, 8),
+            (0, 4, 4, 8),
+            (2, 0, 4, 4),
+            (0, 2, 2, 4),
+            (1, 0, 2, 2),
+            (0, 1, 1, 2),
+        ] {
+            if x0 >= width || y0 >= height {
+                continue;
+            }
+            for y in (y0..height).step_by(dy) {
+                data.push(0);
+                data.extend((x0..width).step_by(dx).map(|x| value(x, y)));
+            }
+        }
+
+        let image = read(&mut file(width, height, [8, 0], 1, &[], &data).as_slice()).unwrap();
+        assert_eq!((image.width(), image.height()), (width, height));
+        for y in 0..height {
+            for x in 0..width {
+                assert_eq!(image[(x, y)], gray(value(x, y)), "({x}, {y})");
+            }
+        }
+
+        // a single pixel only fills the first pass
+        let image = read(&mut file(1, 1, [8, 0], 1, &[], &[0, 42]).as_slice()).unwrap();
+        assert_eq!(image.pixels(), [gray(42)]);
+    }
+
+    #[test]
+    fn test_read_palette() {
+        let plte = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+        // two bits per index, packed from the high bits, the last byte padded
+        let data = [0, 0b00_01_10_01, 0b10_000000];
+
+        let image = read(&mut file(5, 1, [2, 3], 0, &plte, &data).as_slice()).unwrap();
+        let [r, g, b] = [
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ];
+        assert_eq!(image.pixels(), [r, g, b, g, b]);
+
+        let out_of_range = [0, 0b11_000000];
+        assert!(matches!(
+            read(&mut file(1, 1, [2, 3], 0, &plte, &out_of_range).as_slice()),
+            Err(ReadImageError::Malformed(_))
+        ));
+        assert!(matches!(
+            read(&mut file(1, 1, [2, 3], 0, &[], &[0, 0]).as_slice()),
+            Err(ReadImageError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_rejects_dimensions_the_data_doesnt_have() {
+        let malformed = |file: Vec<u8>| {
+            matches!(
+                read(&mut file.as_slice()),
+                Err(ReadImageError::Malformed(_))
+            )
+        };
+
+        // a row short, and a row too many
+        assert!(malformed(file(2, 2, [8, 0], 0, &[], &[0, 1, 2])));
+        assert!(malformed(file(2, 1, [8, 0], 0, &[], &[0, 1, 2, 0, 3, 4])));
+
+        // huge, but with next to no data, which must fail before anything that size exists
+        assert!(malformed(file(
+            i32::MAX as u32,
+            i32::MAX as u32,
+            [16, 6],
+            0,
+            &[],
+            &[0; 16]
+        )));
+        assert!(malformed(file(
+            i32::MAX as u32,
+            i32::MAX as u32,
+            [8, 2],
+            1,
+            &[],
+            &[0; 16]
+        )));
+        assert!(malformed(file(
+            1 << 20,
+            1 << 20,
+            [8, 0],
+            1,
+            &[],
+            &vec![0; 1 << 16]
+        )));
+
+        // outside what the spec allows at all
+        assert!(malformed(file(u32::MAX, 1, [8, 0], 1, &[], &[0, 0])));
+        assert!(malformed(file(0, 1, [8, 0], 0, &[], &[])));
+
+        // cut off partway through a chunk
+        let whole = file(2, 2, [8, 0], 0, &[], &[0, 1, 2, 0, 3, 4]);
+        assert!(read(&mut &whole[..whole.len() - 20]).is_err());
+    }
+
+    #[test]
+    fn test_read_rendered_image() {
+        let file = include_bytes!("../../images/cornell_box_1.png");
+        let image = read(&mut file.as_slice()).unwrap();
+
+        assert_eq!((image.width(), image.height()), (600, 600));
+        assert_eq!(image[(300, 250)], gray(255));
+        assert_eq!(image[(500, 200)] * 255.0, Vector3::new(106.0, 102.0, 31.0));
+
+        // totals from an independent decoder
+        let total = image
+            .pixels()
+            .iter()
+            .fold(Vector3::ZERO, |acc, &p| acc + p * 255.0);
+        let [r, g, b] = [total.x, total.y, total.z].map(f64::round);
+        assert_eq!((r, g, b), (2_679_439.0, 2_397_269.0, 2_124_782.0));
+    }
+}
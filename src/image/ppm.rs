@@ -0,0 +1,305 @@
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Read, Write};
+
+use super::{Image, ReadImageError};
+use crate::{color::DisplayTransform, vector::Vector3};
+
+/// Writes `image` as a plain-text (P3) portable pixmap, one pixel per line.
+pub fn write(writer: &mut impl Write, image: &Image, display: &DisplayTransform) -> io::Result<()> {
+    writeln!(writer, "P3")?;
+    writeln!(writer, "{} {}", image.width(), image.height())?;
+    writeln!(writer, "255")?;
+
+    let mut line = String::new();
+    for row in image.rows() {
+        line.clear();
+        for &color in row {
+            let [r, g, b] = display.to_rgb8(color);
+            // writing into a String can't fail
+            let _ = writeln!(line, "{r} {g} {b}");
+        }
+        writer.write_all(line.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Writes `image` as a binary (P6) portable pixmap.
+pub fn write_binary(
+    writer: &mut impl Write,
+    image: &Image,
+    display: &DisplayTransform,
+) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", image.width(), image.height())?;
+
+    let mut line = Vec::with_capacity(image.width() as usize * 3);
+    for row in image.rows() {
+        line.clear();
+        line.extend(row.iter().flat_map(|&color| display.to_rgb8(color)));
+        writer.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+/// Writes `image` as a little-endian Portable Float Map, keeping linear radiance unclamped.
+pub fn write_float(writer: &mut impl Write, image: &Image) -> io::Result<()> {
+    // a negative scale marks the samples as little-endian
+    write!(writer, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+
+    let mut line = Vec::with_capacity(image.width() as usize * 3 * size_of::<f32>());
+    // scanlines run bottom to top
+    for row in image.rows().rev() {
+        line.clear();
+        for color in row {
+            for component in [color.x, color.y, color.z] {
+                line.extend_from_slice(&(component as f32).to_le_bytes());
+            }
+        }
+        writer.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a plain (P3) or binary (P6) portable pixmap.
+///
+/// Samples are scaled to [0, 1] by the file's maximum value, but otherwise left in whatever
+/// encoding the file uses; nothing is linearized.
+pub fn read(reader: &mut impl BufRead) -> Result<Image, ReadImageError> {
+    let mut header = Header::new(reader);
+
+    let magic = header.token()?;
+    let binary = match magic.as_str() {
+        "P3" => false,
+        "P6" => true,
+        _ => {
+            return Err(ReadImageError::Malformed(format!(
+                "unsupported pixmap magic number {magic:?} (expected P3 or P6)"
+            )));
+        }
+    };
+
+    let width = header.number()?;
+    let height = header.number()?;
+    let max_value = header.number()?;
+    if !(1..=0xffff).contains(&max_value) {
+        return Err(ReadImageError::Malformed(format!(
+            "maximum value {max_value} out of range (expected 1 to 65535)"
+        )));
+    }
+
+    // nothing is allocated for the pixels until the file has shown it really holds them
+    let too_large = || ReadImageError::Malformed(format!("{width}x{height} image is too large"));
+    let pixel_count = (width as usize)
+        .checked_mul(height as usize)
+        .ok_or_else(too_large)?;
+    let scale = 1.0 / f64::from(max_value);
+
+    let pixels = if binary {
+        // exactly one whitespace byte separates the header from the samples
+        header.byte()?;
+
+        let sample_size = if max_value < 256 { 1 } else { 2 };
+        let len = pixel_count
+            .checked_mul(3 * sample_size)
+            .ok_or_else(too_large)?;
+        let mut data = Vec::new();
+        reader.take(len as u64).read_to_end(&mut data)?;
+        if data.len() < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        data.chunks_exact(3 * sample_size)
+            .map(|bytes| {
+                let sample = |i: usize| {
+                    let value = if sample_size == 1 {
+                        u16::from(bytes[i])
+                    } else {
+                        u16::from_be_bytes([bytes[2 * i], bytes[2 * i + 1]])
+                    };
+                    f64::from(value) * scale
+                };
+                Vector3::new(sample(0), sample(1), sample(2))
+            })
+            .collect()
+    } else {
+        let mut sample = || match header.number()? {
+            value if value <= max_value => Ok(f64::from(value) * scale),
+            value => Err(ReadImageError::Malformed(format!(
+                "sample {value} is above the maximum value {max_value}"
+            ))),
+        };
+
+        let mut pixels = Vec::new();
+        for _ in 0..pixel_count {
+            pixels.push(Vector3::new(sample()?, sample()?, sample()?));
+        }
+        pixels
+    };
+
+    Ok(Image::from_pixels(width, height, pixels))
+}
+
+/// Whitespace-separated tokens with `#` comments, as used by netpbm headers.
+struct Header<'r, R: BufRead> {
+    reader: &'r mut R,
+}
+
+impl<'r, R: BufRead> Header<'r, R> {
+    fn new(reader: &'r mut R) -> Self {
+        Self { reader }
+    }
+
+    fn byte(&mut self) -> Result<u8, ReadImageError> {
+        let mut byte = [0u8];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, ReadImageError> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn token(&mut self) -> Result<String, ReadImageError> {
+        let mut token = String::new();
+        while let Some(byte) = self.peek()? {
+            if byte == b'#' {
+                let mut comment = Vec::new();
+                self.reader.read_until(b'\n', &mut comment)?;
+            } else if byte.is_ascii_whitespace() {
+                if !token.is_empty() {
+                    break;
+                }
+                self.reader.consume(1);
+            } else {
+                token.push(char::from(byte));
+                self.reader.consume(1);
+            }
+        }
+
+        if token.is_empty() {
+            Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+        } else {
+            Ok(token)
+        }
+    }
+
+    fn number(&mut self) -> Result<u32, ReadImageError> {
+        let token = self.token()?;
+        token
+            .parse()
+            .map_err(|_| ReadImageError::Malformed(format!("expected a number, found {token:?}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{ToneMapper, TransferFunction};
+
+    fn gradient() -> Image {
+        let mut image = Image::new(5, 3);
+        for (i, pixel) in image.pixels_mut().iter_mut().enumerate() {
+            let i = i as f64;
+            // scaled the same way `read` scales, so the round trip is exact
+            *pixel = Vector3::new(i, 7.0 * i, 255.0 - i) * (1.0 / 255.0);
+        }
+        image
+    }
+
+    fn linear() -> DisplayTransform {
+        DisplayTransform {
+            tone_mapper: ToneMapper::Clamp,
+            transfer: TransferFunction::Linear,
+        }
+    }
+
+    #[test]
+    fn test_read_what_we_write() {
+        let image = gradient();
+
+        let mut plain = Vec::new();
+        write(&mut plain, &image, &linear()).unwrap();
+        assert!(plain.starts_with(b"P3\n5 3\n255\n0 0 255\n"));
+        assert_eq!(read(&mut plain.as_slice()).unwrap(), image);
+
+        let mut binary = Vec::new();
+        write_binary(&mut binary, &image, &linear()).unwrap();
+        let header = b"P6\n5 3\n255\n";
+        assert!(binary.starts_with(header));
+        assert_eq!(binary.len(), header.len() + 5 * 3 * 3);
+        assert_eq!(read(&mut binary.as_slice()).unwrap(), image);
+    }
+
+    #[test]
+    fn test_read_sixteen_bit_binary() {
+        let mut file = b"P6 # comment\n2 1 65535\n".to_vec();
+        file.extend([0xff, 0xff, 0x80, 0x00, 0x00, 0x00]);
+        file.extend([0x00, 0x01, 0x00, 0x00, 0xff, 0xff]);
+
+        let image = read(&mut file.as_slice()).unwrap();
+        assert_eq!(
+            image.pixels(),
+            [
+                Vector3::new(1.0, 32768.0 / 65535.0, 0.0),
+                Vector3::new(1.0 / 65535.0, 0.0, 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_rejects_bad_headers() {
+        for file in [
+            &b"P5\n1 1\n255\n\0"[..],
+            b"P3\n1 1\n0\n",
+            b"P3\n1 x\n255\n",
+            b"P3\n1 1\n255\n0 999 0\n",
+        ] {
+            assert!(matches!(
+                read(&mut &file[..]),
+                Err(ReadImageError::Malformed(_))
+            ));
+        }
+        // too few samples for the dimensions, which mustn't be allocated up front
+        for file in [
+            &b"P6\n2 2\n255\n\0\0\0"[..],
+            b"P6\n100000 100000\n65535\n\0\0\0",
+            b"P3\n100000 100000\n255\n0 0 0\n",
+        ] {
+            assert!(matches!(read(&mut &file[..]), Err(ReadImageError::Io(_))));
+        }
+        assert!(matches!(
+            read(&mut &b"P6\n4294967295 4294967295\n65535\n\0"[..]),
+            Err(ReadImageError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_write_float() {
+        let mut image = gradient();
+        image.pixels_mut()[0] = Vector3::new(-0.5, 1e6, 0.25);
+
+        let mut out = Vec::new();
+        write_float(&mut out, &image).unwrap();
+
+        let header = b"PF\n5 3\n-1.0\n";
+        assert!(out.starts_with(header));
+
+        let samples = out[header.len()..]
+            .as_chunks::<4>()
+            .0
+            .iter()
+            .map(|&bytes| f32::from_le_bytes(bytes))
+            .collect::<Vec<_>>();
+        assert_eq!(samples.len(), 5 * 3 * 3);
+
+        // the last file row is the top of the image, unclamped
+        let rows = image.rows().rev().flatten();
+        for (actual, expected) in samples.as_chunks::<3>().0.iter().zip(rows) {
+            let expected = [expected.x, expected.y, expected.z].map(|x| x as f32);
+            assert_eq!(*actual, expected);
+        }
+        assert_eq!(samples[2 * 5 * 3..][..3], [-0.5, 1e6, 0.25]);
+    }
+}
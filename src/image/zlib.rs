@@ -0,0 +1,631 @@
+//! A small zlib (RFC 1950) stream encoder and decoder.
+//!
+//! Compression is DEFLATE (RFC 1951) using greedy LZ77 matching over hash chains and
+//! the fixed Huffman code tables. That's well short of what a dedicated library would
+//! achieve, but rendered images have enough flat regions that it pays for itself.
+//!
+//! Decompression handles everything DEFLATE allows (stored, fixed and dynamic Huffman
+//! blocks), since we don't get to choose how the images we read were written.
+
+use super::ReadImageError;
+
+const WINDOW_SIZE: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+/// (base length, extra bits) for length codes 257..=285
+const LENGTHS: [(u16, u8); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+
+/// (base distance, extra bits) for distance codes 0..=29
+const DISTANCES: [(u16, u8); 30] = [
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 1),
+    (7, 1),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 4),
+    (49, 4),
+    (65, 5),
+    (97, 5),
+    (129, 6),
+    (193, 6),
+    (257, 7),
+    (385, 7),
+    (513, 8),
+    (769, 8),
+    (1025, 9),
+    (1537, 9),
+    (2049, 10),
+    (3073, 10),
+    (4097, 11),
+    (6145, 11),
+    (8193, 12),
+    (12289, 12),
+    (16385, 13),
+    (24577, 13),
+];
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    // largest n such that 255n(n+1)/2 + (n+1)(MOD-1) fits in a u32
+    const NMAX: usize = 5552;
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(NMAX) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+
+    (b << 16) | a
+}
+
+/// Compresses `data` into a complete zlib stream.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::with_capacity(data.len() / 2 + 64);
+
+    // CMF: deflate with a 32K window; FLG: default compression level, check bits
+    bits.bytes.extend_from_slice(&[0x78, 0x9c]);
+
+    // single final block using the fixed Huffman codes
+    bits.write(1, 1);
+    bits.write(0b01, 2);
+
+    let mut matcher = Matcher::new(data.len());
+    let mut pos = 0;
+    while pos < data.len() {
+        match matcher.longest_match(data, pos) {
+            Some((length, distance)) => {
+                write_match(&mut bits, length, distance);
+                for p in pos..pos + length {
+                    matcher.insert(data, p);
+                }
+                pos += length;
+            }
+            None => {
+                write_literal(&mut bits, u16::from(data[pos]));
+                matcher.insert(data, pos);
+                pos += 1;
+            }
+        }
+    }
+
+    write_literal(&mut bits, 256);
+
+    let mut bytes = bits.finish();
+    bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    bytes
+}
+
+/// Decompresses a complete zlib stream, checking it against its Adler-32 trailer.
+///
+/// Streams that would inflate to more than `max_len` bytes are rejected as soon as they
+/// pass it, so a small malicious stream can't exhaust memory.
+pub fn decompress(data: &[u8], max_len: usize) -> Result<Vec<u8>, ReadImageError> {
+    let [cmf, flg, ..] = *data else {
+        return Err(malformed("truncated zlib header"));
+    };
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 {
+        return Err(malformed(
+            "zlib stream isn't deflate with a window of at most 32K",
+        ));
+    }
+    if (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err(malformed("zlib header check bits don't match"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(malformed(
+            "zlib streams with preset dictionaries aren't supported",
+        ));
+    }
+
+    let mut bits = BitReader::new(&data[2..]);
+    let mut out = Vec::with_capacity(data.len().saturating_mul(4).min(max_len));
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0b00 => inflate_stored(&mut bits, &mut out, max_len)?,
+            0b01 => {
+                let (literals, distances) = fixed_codes();
+                inflate_codes(&mut bits, &mut out, max_len, &literals, &distances)?;
+            }
+            0b10 => {
+                let (literals, distances) = read_dynamic_codes(&mut bits)?;
+                inflate_codes(&mut bits, &mut out, max_len, &literals, &distances)?;
+            }
+            _ => return Err(malformed("reserved deflate block type")),
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    let trailer = bits
+        .remaining_bytes()
+        .get(..4)
+        .ok_or_else(|| malformed("truncated zlib checksum"))?;
+    if u32::from_be_bytes(trailer.try_into().unwrap()) != adler32(&out) {
+        return Err(malformed(
+            "zlib checksum doesn't match the decompressed data",
+        ));
+    }
+
+    Ok(out)
+}
+
+fn malformed(message: impl Into<String>) -> ReadImageError {
+    ReadImageError::Malformed(message.into())
+}
+
+/// Fails if `extra` more bytes would take `out` past `max_len`.
+fn check_len(out: &[u8], extra: usize, max_len: usize) -> Result<(), ReadImageError> {
+    if extra > max_len - out.len() {
+        return Err(malformed(format!(
+            "zlib stream inflates to more than the expected {max_len} bytes"
+        )));
+    }
+    Ok(())
+}
+
+fn inflate_stored(
+    bits: &mut BitReader,
+    out: &mut Vec<u8>,
+    max_len: usize,
+) -> Result<(), ReadImageError> {
+    bits.align_to_byte();
+    let len = bits.read(16)?;
+    let nlen = bits.read(16)?;
+    if len != !nlen & 0xffff {
+        return Err(malformed("stored deflate block length check doesn't match"));
+    }
+    check_len(out, len as usize, max_len)?;
+
+    for _ in 0..len {
+        out.push(bits.read(8)? as u8);
+    }
+
+    Ok(())
+}
+
+fn inflate_codes(
+    bits: &mut BitReader,
+    out: &mut Vec<u8>,
+    max_len: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), ReadImageError> {
+    loop {
+        let symbol = literals.decode(bits)?;
+        let length = match symbol {
+            0..=255 => {
+                check_len(out, 1, max_len)?;
+                out.push(symbol as u8);
+                continue;
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let (base, extra) = LENGTHS[usize::from(symbol - 257)];
+                usize::from(base) + bits.read(u32::from(extra))? as usize
+            }
+            _ => return Err(malformed("invalid deflate length code")),
+        };
+
+        let symbol = distances.decode(bits)?;
+        let &(base, extra) = DISTANCES
+            .get(usize::from(symbol))
+            .ok_or_else(|| malformed("invalid deflate distance code"))?;
+        let distance = usize::from(base) + bits.read(u32::from(extra))? as usize;
+        if distance > out.len() {
+            return Err(malformed(
+                "deflate match reaches back before the stream began",
+            ));
+        }
+
+        check_len(out, length, max_len)?;
+
+        // matches may overlap what they're copying, so this has to go byte by byte
+        let start = out.len() - distance;
+        for i in 0..length {
+            out.push(out[start + i]);
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    // infallible: these are the code lengths the spec defines
+    (
+        Huffman::new(&lengths).unwrap(),
+        Huffman::new(&[5; 30]).unwrap(),
+    )
+}
+
+fn read_dynamic_codes(bits: &mut BitReader) -> Result<(Huffman, Huffman), ReadImageError> {
+    /// Order the code length code lengths are stored in.
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+
+    let n_literals = bits.read(5)? as usize + 257;
+    let n_distances = bits.read(5)? as usize + 1;
+    let n_length_codes = bits.read(4)? as usize + 4;
+
+    let mut length_lengths = [0u8; 19];
+    for &i in &ORDER[..n_length_codes] {
+        length_lengths[i] = bits.read(3)? as u8;
+    }
+    let length_code = Huffman::new(&length_lengths)?;
+
+    // literal and distance code lengths form one sequence, and repeats may straddle them
+    let mut lengths = Vec::with_capacity(n_literals + n_distances);
+    while lengths.len() < n_literals + n_distances {
+        let (length, repeat) = match length_code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let &previous = lengths
+                    .last()
+                    .ok_or_else(|| malformed("deflate code lengths repeat nothing"))?;
+                (previous, 3 + bits.read(2)?)
+            }
+            17 => (0, 3 + bits.read(3)?),
+            _ => (0, 11 + bits.read(7)?),
+        };
+
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths.len() > n_literals + n_distances {
+        return Err(malformed("deflate code lengths overrun their table"));
+    }
+    if lengths[256] == 0 {
+        return Err(malformed("deflate block can't encode its own end"));
+    }
+
+    let (literal_lengths, distance_lengths) = lengths.split_at(n_literals);
+    Ok((
+        Huffman::new(literal_lengths)?,
+        Huffman::new(distance_lengths)?,
+    ))
+}
+
+/// A canonical Huffman code, decoded a bit at a time: enough for the textures we read,
+/// without building lookup tables.
+struct Huffman {
+    /// number of codes of each length
+    counts: [u16; 16],
+    /// symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, ReadImageError> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        counts[0] = 0;
+
+        // incomplete codes are allowed (a lone distance code, say), oversubscribed ones aren't
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = 2 * left - i32::from(count);
+            if left < 0 {
+                return Err(malformed("oversubscribed deflate Huffman code"));
+            }
+        }
+
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+            .filter(|&symbol| lengths[usize::from(symbol)] != 0)
+            .collect();
+        symbols.sort_by_key(|&symbol| lengths[usize::from(symbol)]);
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> Result<u16, ReadImageError> {
+        // codes of each length are consecutive, starting where the previous length's ended
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.read(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(malformed("invalid deflate Huffman code"))
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u64,
+    n_bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buffer: 0,
+            n_bits: 0,
+        }
+    }
+
+    /// Reads `n` bits, least significant bit first.
+    fn read(&mut self, n: u32) -> Result<u32, ReadImageError> {
+        while self.n_bits < n {
+            let &byte = self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| malformed("truncated deflate stream"))?;
+            self.buffer |= u64::from(byte) << self.n_bits;
+            self.n_bits += 8;
+            self.pos += 1;
+        }
+
+        let value = (self.buffer & ((1 << n) - 1)) as u32;
+        self.buffer >>= n;
+        self.n_bits -= n;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        let partial = self.n_bits % 8;
+        self.buffer >>= partial;
+        self.n_bits -= partial;
+    }
+
+    /// Whole bytes not yet consumed, once aligned.
+    fn remaining_bytes(&mut self) -> &'a [u8] {
+        self.align_to_byte();
+        // give back any whole bytes still sitting in the buffer
+        &self.data[self.pos - (self.n_bits / 8) as usize..]
+    }
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    n_bits: u32,
+}
+
+impl BitWriter {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(capacity),
+            buffer: 0,
+            n_bits: 0,
+        }
+    }
+
+    /// Appends the low `n` bits of `value`, least significant bit first.
+    fn write(&mut self, value: u32, n: u32) {
+        self.buffer |= u64::from(value) << self.n_bits;
+        self.n_bits += n;
+        while self.n_bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.n_bits -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting from their most significant bit.
+    fn write_code(&mut self, code: u32, n: u32) {
+        self.write(code.reverse_bits() >> (32 - n), n);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n_bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn write_literal(bits: &mut BitWriter, symbol: u16) {
+    let symbol = u32::from(symbol);
+    match symbol {
+        0..=143 => bits.write_code(0x30 + symbol, 8),
+        144..=255 => bits.write_code(0x190 + symbol - 144, 9),
+        256..=279 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(bits: &mut BitWriter, length: usize, distance: usize) {
+    let length_code = LENGTHS.partition_point(|&(base, _)| usize::from(base) <= length) - 1;
+    let (base, extra) = LENGTHS[length_code];
+    write_literal(bits, 257 + length_code as u16);
+    bits.write((length - usize::from(base)) as u32, u32::from(extra));
+
+    let distance_code = DISTANCES.partition_point(|&(base, _)| usize::from(base) <= distance) - 1;
+    let (base, extra) = DISTANCES[distance_code];
+    bits.write_code(distance_code as u32, 5);
+    bits.write((distance - usize::from(base)) as u32, u32::from(extra));
+}
+
+/// Hash chains over every position inserted so far, most recent first.
+struct Matcher {
+    head: Box<[usize]>,
+    prev: Box<[usize]>,
+}
+
+impl Matcher {
+    const NONE: usize = usize::MAX;
+
+    fn new(len: usize) -> Self {
+        Self {
+            head: vec![Self::NONE; 1 << HASH_BITS].into_boxed_slice(),
+            prev: vec![Self::NONE; len.min(WINDOW_SIZE)].into_boxed_slice(),
+        }
+    }
+
+    fn hash(data: &[u8], pos: usize) -> Option<usize> {
+        let key = data.get(pos..pos + MIN_MATCH)?;
+        let key = u32::from(key[0]) << 16 | u32::from(key[1]) << 8 | u32::from(key[2]);
+        Some((key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize)
+    }
+
+    fn insert(&mut self, data: &[u8], pos: usize) {
+        if let Some(hash) = Self::hash(data, pos) {
+            self.prev[pos % WINDOW_SIZE] = self.head[hash];
+            self.head[hash] = pos;
+        }
+    }
+
+    fn longest_match(&self, data: &[u8], pos: usize) -> Option<(usize, usize)> {
+        let hash = Self::hash(data, pos)?;
+        let max_length = MAX_MATCH.min(data.len() - pos);
+
+        let mut best: Option<(usize, usize)> = None;
+        let mut candidate = self.head[hash];
+        for _ in 0..MAX_CHAIN {
+            if candidate == Self::NONE || pos - candidate > WINDOW_SIZE {
+                break;
+            }
+
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[pos..pos + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            if length >= MIN_MATCH && best.is_none_or(|(best_length, _)| length > best_length) {
+                best = Some((length, pos - candidate));
+                if length == max_length {
+                    break;
+                }
+            }
+
+            let next = self.prev[candidate % WINDOW_SIZE];
+            // the chain slot may have been recycled by a newer position
+            if next != Self::NONE && next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut data: Vec<u8> = (0..100_000u64).map(|i| (i * i / 7 % 251) as u8).collect();
+        data.extend(std::iter::repeat_n(b'x', 40_000));
+
+        assert_eq!(decompress(&compress(&data), data.len()).unwrap(), data);
+        assert_eq!(decompress(&compress(&[]), 0).unwrap(), []);
+    }
+
+    #[test]
+    fn test_dynamic_huffman_block() {
+        // zlib.compress(data, 9), which picks a dynamic code for this input
+        let stream = [
+            0x78, 0xda, 0x25, 0x8a, 0x87, 0x0d, 0x00, 0x30, 0x0c, 0xc2, 0x6e, 0x35, 0xe3, 0xff,
+            0x17, 0x9a, 0xa4, 0x08, 0x21, 0x96, 0x8d, 0x6a, 0x55, 0x70, 0x5c, 0x29, 0xb2, 0x68,
+            0xc6, 0x63, 0x7a, 0xe5, 0x20, 0x7f, 0xda, 0xa8, 0x30, 0x8f, 0x07, 0xbd, 0xe5, 0x16,
+            0xfc,
+        ];
+
+        assert_eq!(
+            decompress(&stream, 100).unwrap(),
+            b"ccabecbebaabaababaabeabcbaedbaaacaebabaaaaadaabeabbababdacba"
+        );
+    }
+
+    #[test]
+    fn test_stored_block() {
+        let stream = [
+            0x78, 0x01, 0x01, 0x07, 0x00, 0xf8, 0xff, b's', b't', b'o', b'r', b'e', b'd', b'!',
+            0x0b, 0xef, 0x02, 0xb3,
+        ];
+
+        assert_eq!(decompress(&stream, 7).unwrap(), b"stored!");
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut stream = compress(b"checksummed");
+        *stream.last_mut().unwrap() ^= 1;
+
+        assert!(matches!(
+            decompress(&stream, 100),
+            Err(ReadImageError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let data = [b'x'; 1000];
+        let stream = compress(&data);
+        assert_eq!(decompress(&stream, 1000).unwrap(), data);
+        assert!(matches!(
+            decompress(&stream, 999),
+            Err(ReadImageError::Malformed(_))
+        ));
+
+        // the same for stored blocks, and for literals
+        let stored = [
+            0x78, 0x01, 0x01, 0x07, 0x00, 0xf8, 0xff, b's', b't', b'o', b'r', b'e', b'd', b'!',
+            0x0b, 0xef, 0x02, 0xb3,
+        ];
+        assert!(decompress(&stored, 6).is_err());
+        assert!(decompress(&compress(b"ab"), 1).is_err());
+    }
+}
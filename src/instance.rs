@@ -0,0 +1,244 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    geometry::Hit,
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    surface::{Hittable, Surface},
+    vector::Transform,
+};
+
+/// A shared object placed in the world by a transform. Any number of instances can refer
+/// to the same object (a single surface, or a whole [`BVH`](crate::bvh::BVH) of them)
+/// without copying it. Objects of different types can be placed side by side as
+/// `Instance<dyn Hittable>`.
+///
+/// Rays are carried into the object's own space to be intersected, and the hit is carried
+/// back out. Emissive surfaces inside are copied out into the world for
+/// [`Lights`](crate::light::Lights), see [`Instance::emitters`].
+pub struct Instance<T: ?Sized> {
+    object: Arc<T>,
+    transform: Transform,
+    bounding_box: AABB,
+}
+
+impl<T: Hittable + ?Sized> Instance<T> {
+    pub fn new(object: Arc<T>, transform: Transform) -> Self {
+        let bounding_box = object.bounding_box().transformed(&transform);
+
+        Self {
+            object,
+            transform,
+            bounding_box,
+        }
+    }
+
+    pub fn object(&self) -> &Arc<T> {
+        &self.object
+    }
+
+    /// Maps the object's space into the world.
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+// derived Clone would needlessly require `T: Clone`
+impl<T: ?Sized> Clone for Instance<T> {
+    fn clone(&self) -> Self {
+        Self {
+            object: Arc::clone(&self.object),
+            transform: self.transform,
+            bounding_box: self.bounding_box.clone(),
+        }
+    }
+}
+
+impl<T: Hittable + ?Sized> Hittable for Instance<T> {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<(Hit, Material)> {
+        // the direction isn't renormalized, so distances along it (and `ray_t`) carry over
+        let to_object = self.transform.inverse();
+        let object_ray = Ray::new(to_object.point(ray.origin), to_object.vector(ray.direction));
+        let (hit, material) = self.object.hit(&object_ray, ray_t)?;
+
+        let hit = Hit {
+            p: ray.at(hit.t),
+            face_normal: self.transform.normal(hit.face_normal).to_unit(),
+            shading_normal: self.transform.normal(hit.shading_normal).to_unit(),
+            ..hit
+        };
+        Some((hit, material))
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bounding_box.clone()
+    }
+
+    /// The object's emitters, moved into the world. Spheres that the transform doesn't
+    /// scale evenly are left out: they still glow, but are only found by scattered rays.
+    fn emitters(&self) -> Vec<Surface> {
+        self.object
+            .emitters()
+            .into_iter()
+            .filter_map(|emitter| {
+                let geometry = emitter.geometry.transformed(&self.transform)?;
+                Some(Surface::new(geometry, emitter.material))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bvh::{BVH, PartitionBy},
+        geometry::Geometry,
+        light::Lights,
+        vector::{Vector3, dot},
+    };
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn assert_near(actual: Vector3, expected: Vector3) {
+        assert!(
+            (actual - expected).length() < 1e-9,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    fn gray() -> Material {
+        Material::Lambertian {
+            albedo: Vector3::new(0.5, 0.5, 0.5).into(),
+        }
+    }
+
+    #[test]
+    fn test_instance_matches_transformed_geometry() {
+        // a unit square in the xy plane, stood up to face +x, stretched and moved
+        let square = Arc::new(Surface::new(
+            Geometry::quadrilateral(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ),
+            gray(),
+        ));
+        let transform = Transform::scaling(Vector3::new(2.0, 3.0, 1.0))
+            .unwrap()
+            .then(Transform::rotation(Vector3::new(0.0, 1.0, 0.0), 90_f64.to_radians()).unwrap())
+            .then(Transform::translation(Vector3::new(5.0, 0.0, 0.0)));
+        let instance = Instance::new(square, transform);
+
+        let expected = Geometry::quadrilateral(
+            Vector3::new(5.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -2.0),
+            Vector3::new(0.0, 3.0, 0.0),
+        );
+
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+        let ray = Ray::new(Vector3::new(10.0, 1.0, -1.5), Vector3::new(-1.0, 0.1, 0.2));
+        let (actual, _) = instance.hit(&ray, &ray_t).unwrap();
+        let expected = expected.hit(&ray, &ray_t).unwrap();
+
+        assert!((actual.t - expected.t).abs() < 1e-9);
+        assert_near(actual.p, expected.p);
+        assert_near(actual.face_normal, expected.face_normal);
+        assert_eq!(actual.front_face, expected.front_face);
+        assert!((actual.alpha - expected.alpha).abs() < 1e-9);
+        assert!((actual.beta - expected.beta).abs() < 1e-9);
+
+        let bounds = instance.bounding_box();
+        assert!(bounds.min().z <= -2.0 && bounds.max().y >= 3.0);
+    }
+
+    #[test]
+    fn test_instance_keeps_normals_perpendicular() {
+        let sphere = Arc::new(Surface::new(
+            Geometry::sphere(Vector3::new(0.0, 0.0, 0.0), 1.0).unwrap(),
+            gray(),
+        ));
+        // squashed into an ellipsoid, whose normals a plain matrix multiply would skew
+        let instance = Instance::new(
+            sphere,
+            Transform::scaling(Vector3::new(4.0, 1.0, 1.0)).unwrap(),
+        );
+
+        let ray = Ray::new(Vector3::new(2.0, 5.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let (hit, _) = instance
+            .hit(&ray, &Interval::new(0.0, f64::INFINITY))
+            .unwrap();
+
+        // the ellipse x^2 / 16 + y^2 = 1 has tangent (4y, -x / 4) at (x, y)
+        let tangent = Vector3::new(4.0 * hit.p.y, -hit.p.x / 4.0, 0.0);
+        assert!(dot(hit.face_normal, tangent).abs() < 1e-9);
+        assert!((hit.face_normal.length() - 1.0).abs() < 1e-9);
+        assert!(hit.face_normal.y > 0.0);
+    }
+
+    #[test]
+    fn test_instanced_emitters_are_lights() {
+        let light = || Material::DiffuseLight {
+            emit: Vector3::new(1.0, 1.0, 1.0).into(),
+        };
+        let object = Arc::new(BVH::from_slice(
+            Box::new([
+                Surface::new(
+                    Geometry::quadrilateral(
+                        Vector3::new(-1.0, -1.0, 0.0),
+                        Vector3::new(2.0, 0.0, 0.0),
+                        Vector3::new(0.0, 2.0, 0.0),
+                    ),
+                    light(),
+                ),
+                Surface::new(
+                    Geometry::sphere(Vector3::new(4.0, 0.0, 0.0), 0.5).unwrap(),
+                    light(),
+                ),
+                Surface::new(
+                    Geometry::sphere(Vector3::new(0.0, 0.0, -3.0), 0.5).unwrap(),
+                    gray(),
+                ),
+            ]),
+            &PartitionBy::LongestAxisBisectSlice,
+        ));
+
+        let evenly = Transform::scaling(Vector3::new(2.0, 2.0, 2.0))
+            .unwrap()
+            .then(Transform::rotation(Vector3::new(1.0, 1.0, 0.0), 0.7).unwrap())
+            .then(Transform::translation(Vector3::new(0.0, 0.0, -10.0)));
+        let unevenly = Transform::scaling(Vector3::new(1.0, 2.0, 1.0))
+            .unwrap()
+            .then(Transform::translation(Vector3::new(20.0, 0.0, 0.0)));
+        assert_eq!(
+            Instance::new(Arc::clone(&object), evenly).emitters().len(),
+            2
+        );
+        // a stretched sphere isn't one any more
+        assert_eq!(
+            Instance::new(Arc::clone(&object), unevenly)
+                .emitters()
+                .len(),
+            1
+        );
+
+        // every sample aimed at a light from outside reaches that same light, where the
+        // instance puts it
+        let world = [Instance::new(object, evenly)];
+        let lights = Lights::from_world(&world.as_slice());
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        for _ in 0..100 {
+            let (light, direction) = lights.sample_direction(Vector3::ZERO, &mut rng).unwrap();
+            let ray = Ray::new(Vector3::ZERO, direction);
+            let (hit, material) = world.as_slice().hit(&ray, &ray_t).unwrap();
+            assert!(matches!(material, Material::DiffuseLight { .. }));
+
+            let (found, pdf) = lights.find(&ray, &hit).unwrap();
+            assert_eq!(found, light);
+            assert!(pdf > 0.0 && pdf.is_finite());
+        }
+    }
+}
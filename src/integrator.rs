@@ -0,0 +1,229 @@
+use rand::Rng;
+
+use crate::{
+    geometry::Hit,
+    interval::Interval,
+    light::Lights,
+    material::Material,
+    ray::Ray,
+    surface::Hittable,
+    vector::{Vector3, dot},
+};
+
+/// An iterative path tracer: follows one scattered ray per bounce, carrying the product of
+/// attenuations along the path as its throughput.
+///
+/// At surfaces that scatter by a density, light reaching the surface directly is estimated
+/// twice: once by sampling the scene's [`Lights`] (next-event estimation), and once by
+/// the scattered ray happening to hit an emitter. The two are combined with the power
+/// heuristic, so each is trusted where its own density is high.
+pub struct PathTracer {
+    /// Paths are cut off after this many segments, contributing nothing further.
+    pub max_depth: u32,
+    /// Bounces before Russian roulette starts terminating low-throughput paths.
+    pub roulette_depth: u32,
+    /// Radiance arriving along rays that escape the scene.
+    pub background: Vector3,
+}
+
+impl PathTracer {
+    /// Highest probability of continuing a path under Russian roulette, so even bright
+    /// paths eventually terminate.
+    const MAX_SURVIVAL: f64 = 0.95;
+
+    fn ray_t() -> Interval {
+        Interval::new(0.001, f64::INFINITY)
+    }
+
+    pub fn radiance<R: Rng + ?Sized>(
+        &self,
+        ray: Ray,
+        world: &impl Hittable,
+        lights: &Lights,
+        rng: &mut R,
+    ) -> Vector3 {
+        let mut ray = ray;
+        let mut radiance = Vector3::ZERO;
+        let mut throughput = Vector3::new(1.0, 1.0, 1.0);
+        // the density the previous vertex scattered `ray` by, if it also sampled the lights
+        let mut scatter_pdf = None;
+
+        for depth in 0..self.max_depth {
+            let Some((hit, material)) = world.hit(&ray, &Self::ray_t()) else {
+                radiance += throughput * self.background;
+                break;
+            };
+
+            let emitted = material.emitted(&ray, &hit);
+            if emitted != Vector3::ZERO {
+                // emitters that aren't among the lights could only have been found this way
+                let weight = scatter_pdf
+                    .zip(lights.find(&ray, &hit))
+                    .map_or(1.0, |(scatter_pdf, (_, light_pdf))| {
+                        power_heuristic(scatter_pdf, light_pdf)
+                    });
+                radiance += throughput * emitted * weight;
+            }
+
+            // delta lobes can only be lit by the rays they scatter themselves
+            let wo = -ray.direction.to_unit();
+            let samples_lights = !lights.is_empty() && !material.is_delta();
+            if samples_lights {
+                radiance += throughput * self.direct_light(wo, &hit, &material, world, lights, rng);
+            }
+
+            let Some(sample) = material.sample(wo, &hit, rng) else {
+                break;
+            };
+            throughput *= sample.weight;
+            scatter_pdf = (samples_lights && !sample.delta).then_some(sample.pdf);
+
+            if depth + 1 >= self.roulette_depth {
+                // surviving paths are reweighted so the estimate stays unbiased
+                let survival = throughput.max_component().min(Self::MAX_SURVIVAL);
+                if rng.random::<f64>() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+
+            ray = Ray::new(hit.p, sample.wi);
+        }
+
+        radiance
+    }
+
+    /// Estimates light scattered along `wo` after arriving directly from the scene's
+    /// lights, with a single shadow ray.
+    fn direct_light<R: Rng + ?Sized>(
+        &self,
+        wo: Vector3,
+        hit: &Hit,
+        material: &Material,
+        world: &impl Hittable,
+        lights: &Lights,
+        rng: &mut R,
+    ) -> Vector3 {
+        let Some((light, direction)) = lights.sample_direction(hit.p, rng) else {
+            return Vector3::ZERO;
+        };
+
+        // lights behind the true surface are blocked by it, whatever the shading normal says
+        let cosine = dot(direction.to_unit(), hit.shading_normal);
+        if cosine <= 0.0 || dot(direction, hit.face_normal) <= 0.0 {
+            return Vector3::ZERO;
+        }
+
+        let bsdf = material.eval(wo, direction, hit);
+        if bsdf == Vector3::ZERO {
+            return Vector3::ZERO;
+        }
+
+        // anything in front of the sampled light blocks it, other lights included, so each
+        // lit point could only have been sampled by aiming at its own light
+        let shadow_ray = Ray::new(hit.p, direction);
+        let Some((light_hit, light_material)) = world.hit(&shadow_ray, &Self::ray_t()) else {
+            return Vector3::ZERO;
+        };
+        let Some((reached, pdf)) = lights.find(&shadow_ray, &light_hit) else {
+            return Vector3::ZERO;
+        };
+        if reached != light || pdf <= 0.0 {
+            return Vector3::ZERO;
+        }
+
+        let emitted = light_material.emitted(&shadow_ray, &light_hit);
+        let weight = power_heuristic(pdf, material.pdf(wo, direction, hit));
+        emitted * bsdf * (cosine * weight / pdf)
+    }
+}
+
+/// Weight for a sample drawn with density `pdf` when another strategy with density `other`
+/// could also have drawn it.
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    if pdf == 0.0 {
+        return 0.0;
+    }
+
+    // as a ratio, so an unbounded density on either side still weighs in sensibly
+    1.0 / (1.0 + (other / pdf).powi(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{geometry::Geometry, surface::Surface};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_power_heuristic() {
+        assert_eq!(power_heuristic(0.0, 1.0), 0.0);
+        assert_eq!(power_heuristic(1.0, 0.0), 1.0);
+        assert_eq!(power_heuristic(2.0, 2.0), 0.5);
+        assert!((power_heuristic(3.0, 1.0) - 0.9).abs() < 1e-12);
+
+        // the two strategies' weights for the same sample sum to one
+        for (a, b) in [(0.1, 7.0), (1e-9, 1e9), (4.0, 4.5)] {
+            assert!((power_heuristic(a, b) + power_heuristic(b, a) - 1.0).abs() < 1e-12);
+        }
+    }
+
+    /// A gray sphere lit evenly from every direction reflects its albedo, whether the light
+    /// comes from the background or from an emitter enclosing the scene, and however soon
+    /// Russian roulette kicks in.
+    #[test]
+    fn test_furnace() {
+        let albedo = 0.8;
+        let sphere = Surface::new(
+            Geometry::sphere(Vector3::ZERO, 1.0).unwrap(),
+            Material::Lambertian {
+                albedo: Vector3::new(albedo, albedo, albedo).into(),
+            },
+        );
+        let enclosure = Surface::new(
+            Geometry::sphere(Vector3::ZERO, 10.0).unwrap(),
+            Material::DiffuseLight {
+                emit: Vector3::new(1.0, 1.0, 1.0).into(),
+            },
+        );
+
+        let lit_by_background = [sphere.clone()];
+        let lit_by_emitter = [sphere, enclosure];
+        for (world, background) in [
+            (lit_by_background.as_slice(), Vector3::new(1.0, 1.0, 1.0)),
+            (lit_by_emitter.as_slice(), Vector3::ZERO),
+        ] {
+            let lights = Lights::from_surfaces(world);
+            for roulette_depth in [0, 100] {
+                let tracer = PathTracer {
+                    max_depth: 100,
+                    roulette_depth,
+                    background,
+                };
+
+                let mut rng = ChaCha8Rng::seed_from_u64(5);
+                let samples = 20_000;
+                let mean = (0..samples)
+                    .map(|_| {
+                        let target = Vector3::new(rng.random_range(-0.5..0.5), 0.0, 0.0);
+                        let origin = Vector3::new(0.0, 0.0, 5.0);
+                        tracer.radiance(
+                            Ray::new(origin, target - origin),
+                            &world,
+                            &lights,
+                            &mut rng,
+                        )
+                    })
+                    .fold(Vector3::ZERO, |acc, e| acc + e)
+                    / samples as f64;
+
+                assert!(
+                    (mean - Vector3::new(albedo, albedo, albedo)).length() < 0.02,
+                    "lights: {}, roulette from {roulette_depth}: {mean:?}",
+                    !lights.is_empty()
+                );
+            }
+        }
+    }
+}
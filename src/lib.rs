@@ -1,10 +1,19 @@
 pub mod aabb;
 pub mod bvh;
 pub mod camera;
+pub mod color;
 pub mod geometry;
+pub mod image;
+pub mod instance;
+pub mod integrator;
 pub mod interval;
+pub mod light;
+pub mod loader;
 pub mod material;
+pub mod mesh;
 pub mod ray;
 pub mod runner;
+pub mod sampling;
 pub mod surface;
+pub mod texture;
 pub mod vector;
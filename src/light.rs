@@ -0,0 +1,160 @@
+use rand::Rng;
+
+use crate::{
+    bvh::{BVH, PartitionBy, SAHBucketStrategy, SAHLeafPolicy},
+    geometry::Hit,
+    interval::Interval,
+    ray::Ray,
+    surface::{Hittable, Surface},
+    vector::Vector3,
+};
+
+/// The emissive surfaces of a scene, kept aside so the integrator can aim rays at them
+/// directly instead of waiting for scattered rays to find them by chance.
+pub struct Lights {
+    /// also tells which light a ray reached, without testing every one
+    surfaces: BVH<Surface>,
+}
+
+impl Default for Lights {
+    fn default() -> Self {
+        Self::from_surfaces(&[])
+    }
+}
+
+impl Lights {
+    /// Collects every surface with a [`Material::DiffuseLight`].
+    pub fn from_surfaces(surfaces: &[Surface]) -> Self {
+        Self::from_world(&surfaces)
+    }
+
+    /// Collects the [`Hittable::emitters`] of `world`, including those of any instances
+    /// in it.
+    pub fn from_world(world: &impl Hittable) -> Self {
+        let lights = world.emitters().into_boxed_slice();
+
+        Self {
+            surfaces: BVH::from_slice(
+                lights,
+                &PartitionBy::SurfaceAreaHeuristic(
+                    SAHBucketStrategy::PerSurface,
+                    SAHLeafPolicy::default(),
+                ),
+            ),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.surfaces.primitives().is_empty()
+    }
+
+    /// Samples a direction from `origin` toward one of the lights, chosen uniformly, and
+    /// says which light that was.
+    pub fn sample_direction<R: Rng + ?Sized>(
+        &self,
+        origin: Vector3,
+        rng: &mut R,
+    ) -> Option<(usize, Vector3)> {
+        let lights = self.surfaces.primitives();
+        if lights.is_empty() {
+            return None;
+        }
+
+        let index = rng.random_range(0..lights.len());
+        Some((index, lights[index].geometry.sample_direction(origin, rng)))
+    }
+
+    /// The light `ray` reached at `hit`, if it's one of these, along with the density,
+    /// with respect to solid angle, of [`Lights::sample_direction`] aiming at it along
+    /// `ray`'s direction.
+    ///
+    /// Only the light that was actually hit counts, so a sample aimed at one light that
+    /// reaches another in front of it should be treated as blocked.
+    pub fn find(&self, ray: &Ray, hit: &Hit) -> Option<(usize, f64)> {
+        // the light is a copy of what was hit, so it should be at the same distance
+        let tolerance = 1e-6 * hit.t.abs().max(1.0);
+        let near_hit = Interval::new(hit.t - tolerance, hit.t + tolerance);
+        let (index, light_hit, _) = self.surfaces.hit_primitive(ray, &near_hit)?;
+
+        let light = &self.surfaces.primitives()[index];
+        let pdf = light.geometry.hit_direction_pdf(ray, &light_hit);
+        Some((index, pdf / self.surfaces.primitives().len() as f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{geometry::Geometry, material::Material};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn panel(z: f64, size: f64) -> Surface {
+        Surface::new(
+            Geometry::quadrilateral(
+                Vector3::new(-size, -size, z),
+                Vector3::new(2.0 * size, 0.0, 0.0),
+                Vector3::new(0.0, 2.0 * size, 0.0),
+            ),
+            Material::DiffuseLight {
+                emit: Vector3::new(1.0, 1.0, 1.0).into(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_find_the_light_that_was_hit() {
+        // a small light in front of a bigger one, and a wall that doesn't emit
+        let wall = Surface::new(
+            Geometry::quadrilateral(
+                Vector3::new(5.0, -1.0, -1.0),
+                Vector3::new(0.0, 2.0, 0.0),
+                Vector3::new(0.0, 0.0, 2.0),
+            ),
+            Material::Lambertian {
+                albedo: Vector3::new(0.5, 0.5, 0.5).into(),
+            },
+        );
+        let scene = [panel(-1.0, 0.5), panel(-3.0, 2.0), wall];
+        let lights = Lights::from_surfaces(&scene);
+        assert_eq!(lights.surfaces.primitives().len(), 2);
+
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let straight_ahead = Ray::new(Vector3::ZERO, Vector3::new(0.0, 0.0, -1.0));
+        let (front, _) = lights
+            .find(
+                &straight_ahead,
+                &panel(-1.0, 0.5).hit(&straight_ahead, &ray_t).unwrap().0,
+            )
+            .unwrap();
+
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let mut blocked = 0;
+        for _ in 0..100 {
+            let (index, direction) = lights.sample_direction(Vector3::ZERO, &mut rng).unwrap();
+            let ray = Ray::new(Vector3::ZERO, direction);
+
+            // whatever the sample reaches first is what's found, at the distance it was hit
+            let (hit, _) = scene.as_slice().hit(&ray, &ray_t).unwrap();
+            let (found, pdf) = lights.find(&ray, &hit).unwrap();
+            let found_light = &lights.surfaces.primitives()[found];
+            assert_eq!(found_light.hit(&ray, &ray_t).unwrap().0.t, hit.t);
+            assert!(found == index || found == front);
+            blocked += (found != index) as u32;
+
+            let expected = found_light.geometry.direction_pdf(Vector3::ZERO, direction) / 2.0;
+            assert!((pdf - expected).abs() <= 1e-12 * expected);
+        }
+        // the front light hides just over half of the one behind it, as seen from here
+        assert!((10..45).contains(&blocked), "{blocked}");
+
+        let to_wall = Ray::new(Vector3::ZERO, Vector3::new(1.0, 0.0, 0.0));
+        let (hit, _) = scene.as_slice().hit(&to_wall, &ray_t).unwrap();
+        assert_eq!(lights.find(&to_wall, &hit), None);
+        assert!(
+            Lights::default()
+                .sample_direction(Vector3::ZERO, &mut rng)
+                .is_none()
+        );
+    }
+}
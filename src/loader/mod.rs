@@ -0,0 +1,110 @@
+//! Importers for models made in other tools.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::{
+    image::ReadImageError,
+    material::Material,
+    mesh::{MeshTriangle, TriangleMesh},
+};
+
+mod mtl;
+pub mod obj;
+pub mod ply;
+
+#[derive(Error, Debug)]
+pub enum LoadModelError {
+    #[error("couldn't read {path:?}")]
+    Io {
+        path: Box<Path>,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{path:?}, line {line}: {message}")]
+    Syntax {
+        path: Box<Path>,
+        line: usize,
+        message: String,
+    },
+    #[error("{path:?}: {message}")]
+    Malformed { path: Box<Path>, message: String },
+    #[error("couldn't load texture {path:?}")]
+    Texture {
+        path: Box<Path>,
+        #[source]
+        source: ReadImageError,
+    },
+}
+
+/// A loaded model: meshes, each with a single material.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Model {
+    pub parts: Vec<Part>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Part {
+    /// the group or object the triangles were declared under, if any
+    pub name: Option<String>,
+    pub mesh: Arc<TriangleMesh>,
+    pub material: Material,
+}
+
+impl Model {
+    /// One primitive per triangle, ready for [`crate::bvh::BVH::from_slice`]. The
+    /// triangles of each part share a single copy of its material.
+    pub fn triangles(&self) -> impl Iterator<Item = MeshTriangle> {
+        self.parts.iter().flat_map(|part| {
+            MeshTriangle::all(Arc::clone(&part.mesh), Arc::new(part.material.clone()))
+        })
+    }
+}
+
+/// Non-blank lines of a text format with `#` comments stripped, alongside their 1-based
+/// line numbers.
+fn statements(
+    reader: impl BufRead,
+    path: &Path,
+) -> impl Iterator<Item = Result<(usize, String), LoadModelError>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter_map(move |(i, line)| match line {
+            Ok(line) => {
+                let statement = line.split('#').next().unwrap_or_default().trim();
+                (!statement.is_empty()).then(|| Ok((i + 1, statement.to_owned())))
+            }
+            Err(source) => Some(Err(LoadModelError::Io {
+                path: path.into(),
+                source,
+            })),
+        })
+}
+
+/// Splits a statement into its keyword and the (trimmed) rest of the line.
+fn keyword(statement: &str) -> (&str, &str) {
+    statement
+        .split_once(char::is_whitespace)
+        .map_or((statement, ""), |(keyword, rest)| (keyword, rest.trim()))
+}
+
+fn parse_number<T: FromStr>(token: &str) -> Result<T, String> {
+    token
+        .parse()
+        .map_err(|_| format!("expected a number, found {token:?}"))
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, LoadModelError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|source| LoadModelError::Io {
+            path: path.into(),
+            source,
+        })
+}
@@ -0,0 +1,173 @@
+//! Wavefront material libraries, as referenced by OBJ files' `mtllib` statements.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    material::Material,
+    texture::{Texture, WrapMode},
+    vector::Vector3,
+};
+
+use super::{LoadModelError, keyword, parse_number, statements};
+
+/// The material used for faces that don't name one, and the diffuse color of materials
+/// that don't give a `Kd`. Matches Blender's default.
+pub const DEFAULT_DIFFUSE: Vector3 = Vector3::new(0.8, 0.8, 0.8);
+
+/// Reads every material in a library at `path`, keyed by name. Texture maps are resolved
+/// relative to the library's directory, and `textures` caches them between libraries so
+/// each image is only decoded once.
+pub fn read(
+    reader: impl BufRead,
+    path: &Path,
+    textures: &mut HashMap<PathBuf, Texture>,
+) -> Result<HashMap<String, Material>, LoadModelError> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Definition)> = None;
+
+    for statement in statements(reader, path) {
+        let (line, statement) = statement?;
+        let syntax = |message: String| LoadModelError::Syntax {
+            path: path.into(),
+            line,
+            message,
+        };
+
+        let (keyword, rest) = keyword(&statement);
+        if keyword == "newmtl" {
+            if rest.is_empty() {
+                return Err(syntax("newmtl needs a name".into()));
+            }
+            if let Some((name, definition)) = current.take() {
+                materials.insert(name, definition.material());
+            }
+            current = Some((rest.to_owned(), Definition::default()));
+            continue;
+        }
+
+        let Some((_, definition)) = current.as_mut() else {
+            return Err(syntax(format!("{keyword} before any newmtl")));
+        };
+        match keyword {
+            "Kd" => definition.diffuse = color(rest).map_err(syntax)?,
+            "Ks" => definition.specular = color(rest).map_err(syntax)?,
+            "Ke" => definition.emission = color(rest).map_err(syntax)?,
+            "Ns" => definition.exponent = parse_number(rest).map_err(syntax)?,
+            "Ni" => definition.refraction_index = Some(parse_number(rest).map_err(syntax)?),
+            "d" => definition.dissolve = parse_number(rest).map_err(syntax)?,
+            "Tr" => definition.dissolve = 1.0 - parse_number::<f64>(rest).map_err(syntax)?,
+            "illum" => definition.illum = Some(parse_number(rest).map_err(syntax)?),
+            "map_Kd" => {
+                if rest.is_empty() || rest.starts_with('-') {
+                    return Err(syntax(format!(
+                        "expected a bare file name after map_Kd, found {rest:?} (options aren't supported)"
+                    )));
+                }
+
+                let texture_path = dir.join(rest);
+                let texture = match textures.get(&texture_path) {
+                    Some(texture) => texture.clone(),
+                    None => {
+                        let texture =
+                            Texture::open(&texture_path, WrapMode::Repeat).map_err(|source| {
+                                LoadModelError::Texture {
+                                    path: texture_path.as_path().into(),
+                                    source,
+                                }
+                            })?;
+                        textures.insert(texture_path, texture.clone());
+                        texture
+                    }
+                };
+                definition.diffuse_map = Some(texture);
+            }
+            // everything else (other maps, Ka, Tf, ...) has no equivalent here
+            _ => {}
+        }
+    }
+
+    if let Some((name, definition)) = current {
+        materials.insert(name, definition.material());
+    }
+
+    Ok(materials)
+}
+
+/// `r [g b]`, where a lone value is a gray.
+fn color(rest: &str) -> Result<Vector3, String> {
+    let values = rest
+        .split_whitespace()
+        .map(parse_number)
+        .collect::<Result<Vec<f64>, _>>()?;
+
+    match values[..] {
+        [gray] => Ok(Vector3::new(gray, gray, gray)),
+        [r, g, b] => Ok(Vector3::new(r, g, b)),
+        _ => Err(format!("expected 1 or 3 color components, found {rest:?}")),
+    }
+}
+
+struct Definition {
+    diffuse: Vector3,
+    diffuse_map: Option<Texture>,
+    specular: Vector3,
+    emission: Vector3,
+    /// Phong specular exponent, 0 to 1000
+    exponent: f64,
+    refraction_index: Option<f64>,
+    /// opacity, 1 for opaque
+    dissolve: f64,
+    illum: Option<u32>,
+}
+
+impl Default for Definition {
+    fn default() -> Self {
+        Self {
+            diffuse: DEFAULT_DIFFUSE,
+            diffuse_map: None,
+            specular: Vector3::ZERO,
+            emission: Vector3::ZERO,
+            exponent: 0.0,
+            refraction_index: None,
+            dissolve: 1.0,
+            illum: None,
+        }
+    }
+}
+
+impl Definition {
+    /// The closest of our materials: emissive ones become lights, transparent ones glass,
+    /// ones whose specular color outshines their diffuse color metal, and anything else
+    /// diffuse.
+    fn material(self) -> Material {
+        if self.emission.max_component() > 0.0 {
+            return Material::DiffuseLight {
+                emit: self.emission.into(),
+            };
+        }
+
+        if self.dissolve < 1.0 || matches!(self.illum, Some(4 | 6 | 7 | 9)) {
+            return Material::Dielectric {
+                refraction_index: self.refraction_index.unwrap_or(1.5),
+            };
+        }
+
+        if self.diffuse_map.is_none()
+            && (self.illum == Some(3)
+                || self.specular.max_component() > self.diffuse.max_component())
+        {
+            // the Blinn-Phong exponent that best matches a lobe of roughness r is 2/r^2 - 2
+            return Material::Metal {
+                albedo: self.specular.into(),
+                fuzz_radius: (2.0 / (self.exponent.max(0.0) + 2.0)).sqrt(),
+            };
+        }
+
+        Material::Lambertian {
+            albedo: self.diffuse_map.unwrap_or(self.diffuse.into()),
+        }
+    }
+}
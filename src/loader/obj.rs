@@ -0,0 +1,368 @@
+//! Wavefront OBJ models, with their MTL material libraries.
+//!
+//! Supports vertex positions, texture coordinates and normals, polygonal faces (split
+//! into fans of triangles), groups and objects, and materials. Curves, lines and points
+//! are skipped.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{material::Material, mesh::TriangleMesh, vector::Vector3};
+
+use super::{LoadModelError, Model, Part, keyword, mtl, open, parse_number, statements};
+
+/// Loads the OBJ file at `path`, along with any material libraries and textures it uses.
+pub fn load(path: impl AsRef<Path>) -> Result<Model, LoadModelError> {
+    let path = path.as_ref();
+    read(open(path)?, path)
+}
+
+/// Reads an OBJ file from `reader`. `path` is used in error messages and to resolve
+/// material libraries, so it needn't exist if the file doesn't use any.
+pub fn read(reader: impl BufRead, path: &Path) -> Result<Model, LoadModelError> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+
+    let mut materials = HashMap::new();
+    let mut textures = HashMap::new();
+
+    let mut name: Option<String> = None;
+    let mut material: Option<String> = None;
+    let mut parts: Vec<PartBuilder> = Vec::new();
+    let mut current_part: Option<usize> = None;
+
+    for statement in statements(reader, path) {
+        let (line, statement) = statement?;
+        let syntax = |message: String| LoadModelError::Syntax {
+            path: path.into(),
+            line,
+            message,
+        };
+
+        let (keyword, rest) = keyword(&statement);
+        match keyword {
+            "v" => positions.push(vector(rest).map_err(syntax)?),
+            "vn" => normals.push(vector(rest).map_err(syntax)?),
+            "vt" => {
+                // an optional third (w) coordinate is for 3D textures, which we don't have
+                let coordinates = rest
+                    .split_whitespace()
+                    .map(parse_number)
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(syntax)?;
+                match coordinates[..] {
+                    [u] => uvs.push((u, 0.0)),
+                    [u, v] | [u, v, _] => uvs.push((u, v)),
+                    _ => {
+                        return Err(syntax(format!(
+                            "expected 1 to 3 coordinates, found {rest:?}"
+                        )));
+                    }
+                }
+            }
+            "f" => {
+                let corners = rest
+                    .split_whitespace()
+                    .map(|corner| Corner::parse(corner, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(syntax)?;
+
+                let part = *current_part.get_or_insert_with(|| {
+                    parts
+                        .iter()
+                        .position(|part| part.name == name && part.material == material)
+                        .unwrap_or_else(|| {
+                            parts.push(PartBuilder::new(name.clone(), material.clone()));
+                            parts.len() - 1
+                        })
+                });
+                parts[part].push_polygon(&corners).map_err(syntax)?;
+            }
+            "g" | "o" => {
+                name = (!rest.is_empty()).then(|| rest.to_owned());
+                current_part = None;
+            }
+            "usemtl" => {
+                if !materials.contains_key(rest) {
+                    return Err(syntax(format!("unknown material {rest:?}")));
+                }
+                material = Some(rest.to_owned());
+                current_part = None;
+            }
+            "mtllib" => {
+                for library in rest.split_whitespace() {
+                    let library = dir.join(library);
+                    materials.extend(mtl::read(open(&library)?, &library, &mut textures)?);
+                }
+            }
+            // smoothing groups, free-form geometry, lines, points, ...
+            _ => {}
+        }
+    }
+
+    let default_material = Material::Lambertian {
+        albedo: mtl::DEFAULT_DIFFUSE.into(),
+    };
+    let parts = parts
+        .into_iter()
+        .filter(|part| !part.indices.is_empty())
+        .map(|part| {
+            let material = part
+                .material
+                .as_ref()
+                .map_or(&default_material, |name| &materials[name])
+                .clone();
+            Part {
+                name: part.name.clone(),
+                mesh: Arc::new(part.mesh(&positions, &uvs, &normals)),
+                material,
+            }
+        })
+        .collect();
+
+    Ok(Model { parts })
+}
+
+fn vector(rest: &str) -> Result<Vector3, String> {
+    let values = rest
+        .split_whitespace()
+        .map(parse_number)
+        .collect::<Result<Vec<f64>, _>>()?;
+
+    // positions may carry a trailing weight, or vertex colors, which we ignore
+    match values[..] {
+        [x, y, z, ..] => Ok(Vector3::new(x, y, z)),
+        _ => Err(format!("expected 3 coordinates, found {rest:?}")),
+    }
+}
+
+/// 0-based indices into the position, texture coordinate and normal lists.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+impl Corner {
+    /// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, where each index counts from 1, or back
+    /// from the most recent element if negative.
+    fn parse(token: &str, positions: usize, uvs: usize, normals: usize) -> Result<Self, String> {
+        let mut indices = token.split('/');
+        let mut next = |kind: &str, len: usize| match indices.next() {
+            None | Some("") => Ok(None),
+            Some(index) => resolve(index, kind, len).map(Some),
+        };
+
+        let position = next("position", positions)?
+            .ok_or_else(|| format!("face vertex {token:?} is missing a position"))?;
+        let uv = next("texture coordinate", uvs)?;
+        let normal = next("normal", normals)?;
+
+        if indices.next().is_some() {
+            return Err(format!("face vertex {token:?} has too many indices"));
+        }
+
+        Ok(Self {
+            position,
+            uv,
+            normal,
+        })
+    }
+}
+
+fn resolve(index: &str, kind: &str, len: usize) -> Result<usize, String> {
+    let index: i64 = parse_number(index)?;
+    let resolved = if index < 0 {
+        len.checked_sub(index.unsigned_abs() as usize)
+    } else {
+        (index as usize).checked_sub(1).filter(|&i| i < len)
+    };
+
+    resolved.ok_or_else(|| format!("{kind} {index} doesn't exist (there are {len} so far)"))
+}
+
+/// Triangles sharing a name and a material, with their corners deduplicated into mesh
+/// vertices.
+struct PartBuilder {
+    name: Option<String>,
+    material: Option<String>,
+    corners: Vec<Corner>,
+    vertices: HashMap<Corner, u32>,
+    indices: Vec<[u32; 3]>,
+}
+
+impl PartBuilder {
+    fn new(name: Option<String>, material: Option<String>) -> Self {
+        Self {
+            name,
+            material,
+            corners: Vec::new(),
+            vertices: HashMap::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn push_polygon(&mut self, corners: &[Corner]) -> Result<(), String> {
+        if corners.len() < 3 {
+            return Err(format!(
+                "a face needs at least 3 vertices, found {}",
+                corners.len()
+            ));
+        }
+
+        let indices = corners
+            .iter()
+            .map(|&corner| match self.vertices.entry(corner) {
+                Entry::Occupied(entry) => Ok(*entry.get()),
+                Entry::Vacant(entry) => {
+                    let index = u32::try_from(self.corners.len())
+                        .map_err(|_| "a part can't have more than 2^32 distinct vertices")?;
+                    self.corners.push(corner);
+                    Ok(*entry.insert(index))
+                }
+            })
+            .collect::<Result<Vec<u32>, String>>()?;
+
+        for i in 1..indices.len() - 1 {
+            self.indices.push([indices[0], indices[i], indices[i + 1]]);
+        }
+        Ok(())
+    }
+
+    /// Texture coordinates and normals are only kept if every vertex has them.
+    fn mesh(self, positions: &[Vector3], uvs: &[(f64, f64)], normals: &[Vector3]) -> TriangleMesh {
+        let mesh_uvs: Option<Box<[_]>> = self
+            .corners
+            .iter()
+            .map(|corner| corner.uv.map(|i| uvs[i]))
+            .collect();
+        let mesh_normals: Option<Box<[_]>> = self
+            .corners
+            .iter()
+            .map(|corner| corner.normal.map(|i| normals[i]))
+            .collect();
+        let mesh_positions = self
+            .corners
+            .iter()
+            .map(|corner| positions[corner.position])
+            .collect();
+
+        TriangleMesh::new(
+            mesh_positions,
+            self.indices.into_boxed_slice(),
+            mesh_normals,
+            mesh_uvs,
+        )
+        .expect("corners are resolved against the vertex lists as they're parsed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{loader::LoadModelError, texture::Texture};
+
+    fn parse(source: &str) -> Result<Model, LoadModelError> {
+        read(source.as_bytes(), Path::new("test.obj"))
+    }
+
+    #[test]
+    fn test_shares_vertices_between_triangles() {
+        let model = parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+             vn 0 0 1\n\
+             f 1/1/1 2/2/1 3/3/1 -1/-1/-1\n",
+        )
+        .unwrap();
+
+        assert_eq!(model.parts.len(), 1);
+        let mesh = &model.parts[0].mesh;
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.indices(), &[[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.uvs().unwrap()[2], (1.0, 1.0));
+        assert_eq!(mesh.normals().unwrap()[3], Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_drops_attributes_some_vertices_lack() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/1 2 3\n").unwrap();
+
+        let mesh = &model.parts[0].mesh;
+        assert!(mesh.uvs().is_none());
+        assert!(mesh.normals().is_none());
+    }
+
+    #[test]
+    fn test_loads_groups_and_materials() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/obj/materials.obj");
+        let model = load(path).unwrap();
+
+        let parts: Vec<_> = model
+            .parts
+            .iter()
+            .map(|part| (part.name.as_deref().unwrap(), part.mesh.triangle_count()))
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                ("matte_cube", 12),
+                ("lamp", 2),
+                ("panels", 1),
+                ("panels", 1)
+            ]
+        );
+
+        assert_eq!(
+            model.parts[0].material,
+            Material::Lambertian {
+                albedo: Texture::from(Vector3::new(0.1, 0.2, 0.3))
+            }
+        );
+        assert!(matches!(
+            model.parts[1].material,
+            Material::DiffuseLight { .. }
+        ));
+        assert!(matches!(
+            model.parts[2].material,
+            Material::Metal { fuzz_radius, .. } if (fuzz_radius - 0.1).abs() < 1e-12
+        ));
+        assert_eq!(
+            model.parts[3].material,
+            Material::Dielectric {
+                refraction_index: 1.33
+            }
+        );
+        assert_eq!(model.triangles().count(), 16);
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        let error = parse("v 0 0 0\n# a comment\n\nf 1 2 3\n").unwrap_err();
+        assert!(matches!(error, LoadModelError::Syntax { line: 4, .. }));
+        assert_eq!(
+            error.to_string(),
+            "\"test.obj\", line 4: position 2 doesn't exist (there are 1 so far)"
+        );
+
+        assert!(matches!(
+            parse("v 0 0 0\nusemtl missing\n"),
+            Err(LoadModelError::Syntax { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse("v 0 0 zero\n"),
+            Err(LoadModelError::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse("v 0 0 0\nv 1 0 0\nf 1 2\n"),
+            Err(LoadModelError::Syntax { line: 3, .. })
+        ));
+    }
+}
@@ -0,0 +1,634 @@
+//! Stanford PLY polygon files, in ASCII or binary of either byte order.
+//!
+//! Reads vertex positions, and normals, colors and texture coordinates where present,
+//! along with polygonal faces (split into fans of triangles). Any other elements and
+//! properties, like scanner confidence values or edges, are skipped.
+
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{
+    color::TransferFunction,
+    material::Material,
+    mesh::{MeshTriangle, TriangleMesh},
+    vector::Vector3,
+};
+
+use super::{LoadModelError, keyword, open, parse_number};
+
+/// A mesh read from a PLY file, with its vertex colors if it had any.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Ply {
+    pub mesh: Arc<TriangleMesh>,
+    /// linear RGB, parallel to the mesh's positions
+    pub colors: Option<Box<[Vector3]>>,
+}
+
+impl Ply {
+    /// One primitive per triangle, all sharing `material`.
+    pub fn triangles(&self, material: Material) -> impl Iterator<Item = MeshTriangle> {
+        MeshTriangle::all(Arc::clone(&self.mesh), Arc::new(material))
+    }
+
+    /// One diffuse primitive per triangle, colored with the average of its corners' colors,
+    /// or `None` if the file had no vertex colors. Each triangle's color is a material of
+    /// its own, so these take more memory than [`Ply::triangles`].
+    pub fn colored_triangles(&self) -> Option<impl Iterator<Item = MeshTriangle>> {
+        let colors = self.colors.as_ref()?;
+        let materials = self.mesh.indices().iter().map(|corners| {
+            let color = corners
+                .iter()
+                .map(|&i| colors[i as usize])
+                .fold(Vector3::ZERO, |sum, color| sum + color)
+                / 3.0;
+            Arc::new(Material::Lambertian {
+                albedo: color.into(),
+            })
+        });
+
+        Some(materials.enumerate().filter_map(|(index, material)| {
+            MeshTriangle::new(Arc::clone(&self.mesh), material, index)
+        }))
+    }
+}
+
+/// Loads the PLY file at `path`.
+pub fn load(path: impl AsRef<Path>) -> Result<Ply, LoadModelError> {
+    let path = path.as_ref();
+    read(open(path)?, path)
+}
+
+/// Reads a PLY file from `reader`. `path` is only used in error messages.
+pub fn read(mut reader: impl BufRead, path: &Path) -> Result<Ply, LoadModelError> {
+    let header = Header::read(&mut reader, path)?;
+    let malformed = |message: String| LoadModelError::Malformed {
+        path: path.into(),
+        message,
+    };
+
+    let mut body = Body {
+        reader,
+        path,
+        encoding: header.encoding,
+        line: header.lines,
+        tokens: Vec::new(),
+        next_token: 0,
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut colors = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    let mut found_faces = false;
+
+    let mut scalars = Vec::new();
+    let mut polygon = Vec::new();
+    let mut skipped = Vec::new();
+
+    for element in &header.elements {
+        let vertex = (element.name == "vertex").then(|| VertexLayout::new(element, path));
+        let vertex = vertex.transpose()?;
+        let face = if element.name == "face" {
+            found_faces = true;
+            Some(face_indices_property(element, path)?)
+        } else {
+            None
+        };
+
+        scalars.resize(element.properties.len(), 0.0);
+        for _ in 0..element.count {
+            body.start_element()?;
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    PropertyKind::Scalar(ty) => scalars[i] = body.scalar(ty)?,
+                    PropertyKind::List { count, item } => {
+                        let len = body.scalar(count)?;
+                        if len < 0.0 || len.fract() != 0.0 {
+                            return Err(body.error(format!("invalid list length {len}")));
+                        }
+
+                        // lists other than the face's indices are read and dropped
+                        let values = if face == Some(i) {
+                            &mut polygon
+                        } else {
+                            &mut skipped
+                        };
+                        values.clear();
+                        for _ in 0..len as usize {
+                            values.push(body.scalar(item)?);
+                        }
+                    }
+                }
+            }
+            body.finish_element()?;
+
+            if let Some(layout) = &vertex {
+                let vector =
+                    |[x, y, z]: [usize; 3]| Vector3::new(scalars[x], scalars[y], scalars[z]);
+                positions.push(vector(layout.position));
+                if let Some(normal) = layout.normal {
+                    normals.push(vector(normal));
+                }
+                if let Some((channels, scale)) = layout.color {
+                    let decode = |c: usize| TransferFunction::Srgb.decode(scalars[c] * scale);
+                    colors.push(Vector3::new(
+                        decode(channels[0]),
+                        decode(channels[1]),
+                        decode(channels[2]),
+                    ));
+                }
+                if let Some([u, v]) = layout.uv {
+                    uvs.push((scalars[u], scalars[v]));
+                }
+            }
+
+            if face.is_some() {
+                if polygon.len() < 3 {
+                    return Err(body.error(format!(
+                        "a face needs at least 3 vertices, found {}",
+                        polygon.len()
+                    )));
+                }
+                let corners = polygon
+                    .iter()
+                    .map(|&index| {
+                        if index >= 0.0 && index.fract() == 0.0 && index <= f64::from(u32::MAX) {
+                            Ok(index as u32)
+                        } else {
+                            Err(body.error(format!("invalid vertex index {index}")))
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                for i in 1..corners.len() - 1 {
+                    indices.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+        }
+    }
+
+    if !found_faces {
+        return Err(malformed("there's no face element".into()));
+    }
+
+    // a missing attribute leaves its list empty
+    let mesh = TriangleMesh::new(
+        positions.into(),
+        indices.into(),
+        (!normals.is_empty()).then(|| normals.into()),
+        (!uvs.is_empty()).then(|| uvs.into()),
+    )
+    .map_err(|error| malformed(error.to_string()))?;
+
+    Ok(Ply {
+        mesh: Arc::new(mesh),
+        colors: (!colors.is_empty()).then(|| colors.into()),
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Encoding {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "char" | "int8" => Ok(ScalarType::I8),
+            "uchar" | "uint8" => Ok(ScalarType::U8),
+            "short" | "int16" => Ok(ScalarType::I16),
+            "ushort" | "uint16" => Ok(ScalarType::U16),
+            "int" | "int32" => Ok(ScalarType::I32),
+            "uint" | "uint32" => Ok(ScalarType::U32),
+            "float" | "float32" => Ok(ScalarType::F32),
+            "double" | "float64" => Ok(ScalarType::F64),
+            _ => Err(format!("unknown property type {name:?}")),
+        }
+    }
+
+    /// What to multiply a color channel of this type by to bring it into [0, 1].
+    fn color_scale(self) -> f64 {
+        match self {
+            ScalarType::U8 => 1.0 / f64::from(u8::MAX),
+            ScalarType::U16 => 1.0 / f64::from(u16::MAX),
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum PropertyKind {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    /// The position of the scalar property called `name`, if there is one.
+    fn scalar(&self, name: &str, path: &Path) -> Result<Option<usize>, LoadModelError> {
+        let Some(i) = self.properties.iter().position(|p| p.name == name) else {
+            return Ok(None);
+        };
+
+        match self.properties[i].kind {
+            PropertyKind::Scalar(_) => Ok(Some(i)),
+            PropertyKind::List { .. } => Err(LoadModelError::Malformed {
+                path: path.into(),
+                message: format!("{} property {name:?} is a list", self.name),
+            }),
+        }
+    }
+
+    /// The positions of the properties called each of `names`, if they're all present.
+    fn scalars<const N: usize>(
+        &self,
+        names: [&str; N],
+        path: &Path,
+    ) -> Result<Option<[usize; N]>, LoadModelError> {
+        let mut positions = [0; N];
+        for (position, name) in positions.iter_mut().zip(names) {
+            match self.scalar(name, path)? {
+                Some(i) => *position = i,
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(positions))
+    }
+}
+
+struct Header {
+    encoding: Encoding,
+    elements: Vec<Element>,
+    /// how many lines the header took up, for numbering lines in an ASCII body
+    lines: usize,
+}
+
+impl Header {
+    fn read(reader: &mut impl BufRead, path: &Path) -> Result<Self, LoadModelError> {
+        let mut encoding = None;
+        let mut elements: Vec<Element> = Vec::new();
+
+        let mut text = String::new();
+        let mut line = 0;
+        loop {
+            text.clear();
+            line += 1;
+            let read = reader
+                .read_line(&mut text)
+                .map_err(|source| LoadModelError::Io {
+                    path: path.into(),
+                    source,
+                })?;
+            let syntax = |message: String| LoadModelError::Syntax {
+                path: path.into(),
+                line,
+                message,
+            };
+            if read == 0 {
+                return Err(syntax("the header has no end_header".into()));
+            }
+
+            let statement = text.trim();
+            if line == 1 {
+                if statement != "ply" {
+                    return Err(syntax(format!("expected \"ply\", found {statement:?}")));
+                }
+                continue;
+            }
+
+            let (keyword, rest) = keyword(statement);
+            let words: Vec<&str> = rest.split_whitespace().collect();
+            match keyword {
+                "format" => {
+                    encoding = Some(match words[..] {
+                        ["ascii", "1.0"] => Encoding::Ascii,
+                        ["binary_little_endian", "1.0"] => Encoding::BinaryLittleEndian,
+                        ["binary_big_endian", "1.0"] => Encoding::BinaryBigEndian,
+                        _ => return Err(syntax(format!("unsupported format {rest:?}"))),
+                    });
+                }
+                "element" => {
+                    let [name, count] = words[..] else {
+                        return Err(syntax(format!(
+                            "expected a name and a count, found {rest:?}"
+                        )));
+                    };
+                    elements.push(Element {
+                        name: name.to_owned(),
+                        count: parse_number(count).map_err(syntax)?,
+                        properties: Vec::new(),
+                    });
+                }
+                "property" => {
+                    let Some(element) = elements.last_mut() else {
+                        return Err(syntax("property before any element".into()));
+                    };
+                    let (kind, name) = match words[..] {
+                        ["list", count, item, name] => (
+                            PropertyKind::List {
+                                count: ScalarType::parse(count).map_err(syntax)?,
+                                item: ScalarType::parse(item).map_err(syntax)?,
+                            },
+                            name,
+                        ),
+                        [ty, name] => (
+                            PropertyKind::Scalar(ScalarType::parse(ty).map_err(syntax)?),
+                            name,
+                        ),
+                        _ => {
+                            return Err(syntax(format!(
+                                "expected a type and a name, found {rest:?}"
+                            )));
+                        }
+                    };
+                    element.properties.push(Property {
+                        name: name.to_owned(),
+                        kind,
+                    });
+                }
+                "end_header" => break,
+                "comment" | "obj_info" | "" => {}
+                _ => return Err(syntax(format!("unexpected {keyword:?} in the header"))),
+            }
+        }
+
+        let encoding = encoding.ok_or_else(|| LoadModelError::Malformed {
+            path: path.into(),
+            message: "the header has no format".into(),
+        })?;
+
+        Ok(Self {
+            encoding,
+            elements,
+            lines: line,
+        })
+    }
+}
+
+/// Where a vertex element keeps the attributes we read.
+struct VertexLayout {
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    /// the channels, and the scale bringing them into [0, 1]
+    color: Option<([usize; 3], f64)>,
+    uv: Option<[usize; 2]>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element, path: &Path) -> Result<Self, LoadModelError> {
+        let position =
+            element
+                .scalars(["x", "y", "z"], path)?
+                .ok_or_else(|| LoadModelError::Malformed {
+                    path: path.into(),
+                    message: "vertices need x, y and z properties".into(),
+                })?;
+
+        let color = element
+            .scalars(["red", "green", "blue"], path)?
+            .map(|channels| {
+                let PropertyKind::Scalar(ty) = element.properties[channels[0]].kind else {
+                    unreachable!("scalars only finds scalar properties")
+                };
+                (channels, ty.color_scale())
+            });
+
+        let mut uv = None;
+        for names in [
+            ["u", "v"],
+            ["s", "t"],
+            ["texture_u", "texture_v"],
+            ["texture_s", "texture_t"],
+        ] {
+            uv = element.scalars(names, path)?;
+            if uv.is_some() {
+                break;
+            }
+        }
+
+        Ok(Self {
+            position,
+            normal: element.scalars(["nx", "ny", "nz"], path)?,
+            color,
+            uv,
+        })
+    }
+}
+
+/// The position of a face element's vertex index list.
+fn face_indices_property(element: &Element, path: &Path) -> Result<usize, LoadModelError> {
+    element
+        .properties
+        .iter()
+        .position(|property| {
+            matches!(property.kind, PropertyKind::List { .. })
+                && (property.name == "vertex_indices" || property.name == "vertex_index")
+        })
+        .ok_or_else(|| LoadModelError::Malformed {
+            path: path.into(),
+            message: "faces need a vertex_indices list".into(),
+        })
+}
+
+/// The data following the header, read one element at a time.
+struct Body<'p, R: BufRead> {
+    reader: R,
+    path: &'p Path,
+    encoding: Encoding,
+    /// the current line, in ASCII files
+    line: usize,
+    /// the current line's words, in ASCII files
+    tokens: Vec<String>,
+    next_token: usize,
+}
+
+impl<R: BufRead> Body<'_, R> {
+    fn start_element(&mut self) -> Result<(), LoadModelError> {
+        if self.encoding != Encoding::Ascii {
+            return Ok(());
+        }
+
+        let mut text = String::new();
+        loop {
+            text.clear();
+            self.line += 1;
+            let read = self
+                .reader
+                .read_line(&mut text)
+                .map_err(|source| self.io(source))?;
+            if read == 0 {
+                return Err(self.error("the file ends before all its elements".into()));
+            }
+            if !text.trim().is_empty() {
+                break;
+            }
+        }
+
+        self.tokens = text.split_whitespace().map(str::to_owned).collect();
+        self.next_token = 0;
+        Ok(())
+    }
+
+    fn finish_element(&mut self) -> Result<(), LoadModelError> {
+        if self.encoding == Encoding::Ascii && self.next_token < self.tokens.len() {
+            return Err(self.error(format!(
+                "expected {} values, found {}",
+                self.next_token,
+                self.tokens.len()
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn scalar(&mut self, ty: ScalarType) -> Result<f64, LoadModelError> {
+        if self.encoding == Encoding::Ascii {
+            let Some(token) = self.tokens.get(self.next_token) else {
+                return Err(self.error("too few values".into()));
+            };
+            self.next_token += 1;
+            return parse_number(token).map_err(|message| self.error(message));
+        }
+
+        let big_endian = self.encoding == Encoding::BinaryBigEndian;
+        macro_rules! read {
+            ($t:ty) => {{
+                let mut bytes = [0; size_of::<$t>()];
+                self.reader
+                    .read_exact(&mut bytes)
+                    .map_err(|source| self.io(source))?;
+                if big_endian {
+                    <$t>::from_be_bytes(bytes)
+                } else {
+                    <$t>::from_le_bytes(bytes)
+                }
+            }};
+        }
+
+        Ok(match ty {
+            ScalarType::I8 => f64::from(read!(i8)),
+            ScalarType::U8 => f64::from(read!(u8)),
+            ScalarType::I16 => f64::from(read!(i16)),
+            ScalarType::U16 => f64::from(read!(u16)),
+            ScalarType::I32 => f64::from(read!(i32)),
+            ScalarType::U32 => f64::from(read!(u32)),
+            ScalarType::F32 => f64::from(read!(f32)),
+            ScalarType::F64 => read!(f64),
+        })
+    }
+
+    fn error(&self, message: String) -> LoadModelError {
+        match self.encoding {
+            Encoding::Ascii => LoadModelError::Syntax {
+                path: self.path.into(),
+                line: self.line,
+                message,
+            },
+            _ => LoadModelError::Malformed {
+                path: self.path.into(),
+                message,
+            },
+        }
+    }
+
+    fn io(&self, source: std::io::Error) -> LoadModelError {
+        LoadModelError::Io {
+            path: self.path.into(),
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/ply")
+            .join(name)
+    }
+
+    #[test]
+    fn test_encodings_agree() {
+        let ascii = load(fixture("tetrahedron_ascii.ply")).unwrap();
+        assert_eq!(load(fixture("tetrahedron_binary_le.ply")).unwrap(), ascii);
+        assert_eq!(load(fixture("tetrahedron_binary_be.ply")).unwrap(), ascii);
+
+        let mesh = &ascii.mesh;
+        assert_eq!(mesh.positions()[3], Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            mesh.indices(),
+            &[[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]]
+        );
+        assert_eq!(mesh.normals().unwrap()[0], Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(mesh.uvs().unwrap()[3], (1.0, 1.0));
+
+        let colors = ascii.colors.as_ref().unwrap();
+        assert_eq!(colors[1], Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(ascii.colored_triangles().unwrap().count(), 4);
+    }
+
+    #[test]
+    fn test_triangulates_polygons() {
+        let square = load(fixture("square_ascii.ply")).unwrap();
+
+        assert_eq!(square.mesh.indices(), &[[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(square.mesh.uvs().unwrap()[2], (1.0, 1.0));
+        assert!(square.mesh.normals().is_none());
+        assert!(square.colors.is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let parse = |source: &str| read(source.as_bytes(), Path::new("test.ply"));
+        let header = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n\
+                      property float y\nproperty float z\nelement face 1\n\
+                      property list uchar int vertex_indices\nend_header\n";
+
+        assert!(matches!(
+            parse(&format!("{header}0 0 zero\n3 0 0 0\n")),
+            Err(LoadModelError::Syntax { line: 10, .. })
+        ));
+        assert!(matches!(
+            parse(&format!("{header}0 0 0\n3 0 0 1\n")),
+            Err(LoadModelError::Malformed { .. })
+        ));
+        assert!(matches!(
+            parse(&format!("{header}0 0 0\n")),
+            Err(LoadModelError::Syntax { line: 11, .. })
+        ));
+        assert!(matches!(
+            parse("ply\nformat binary_middle_endian 1.0\nend_header\n"),
+            Err(LoadModelError::Syntax { line: 2, .. })
+        ));
+    }
+}
@@ -1,39 +1,118 @@
-use crate::{geometry::Hit, ray::Ray, vector::Vector3};
+use rand::Rng;
 
+use crate::{
+    geometry::Hit,
+    ray::Ray,
+    texture::Texture,
+    vector::{Vector3, dot},
+};
+
+/// A direction drawn by [`Material::sample`].
+///
+/// Directions follow the usual convention: `wo` points back toward where light is going
+/// (the viewer), and `wi` toward where it came from.
 #[derive(Clone, Debug)]
-pub struct Scatter {
-    pub ray: Ray,
-    pub attenuation: Vector3,
+pub struct BsdfSample {
+    pub wi: Vector3,
+    /// The BSDF for this pair of directions. For delta lobes, this is the lobe's weight
+    /// divided by the cosine, so it still combines with `pdf` the same way.
+    pub f: Vector3,
+    /// Density of drawing `wi`, with respect to solid angle. For delta lobes, the
+    /// probability of picking that lobe instead.
+    pub pdf: f64,
+    /// `f * |cos| / pdf`: what a path's throughput is multiplied by for following `wi`.
+    /// Computed directly, since the ratio is often far better behaved than its parts.
+    pub weight: Vector3,
+    /// Whether `wi` was picked from isolated directions (perfect mirror or refraction),
+    /// which no other sampling strategy could ever reproduce.
+    pub delta: bool,
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Material {
-    Lambertian { albedo: Vector3 },
-    Metal { albedo: Vector3, fuzz_radius: f64 },
+    Lambertian { albedo: Texture },
+    Metal { albedo: Texture, fuzz_radius: f64 },
     Dielectric { refraction_index: f64 },
 
-    DiffuseLight { emit: Vector3 },
+    DiffuseLight { emit: Texture },
     UVGradient { intensity: f64 },
 }
 
 impl Material {
-    pub fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<Scatter> {
-        match *self {
-            Material::Lambertian { albedo } => lambertian::scatter(albedo, ray, hit),
+    /// The BSDF for light arriving from `wi` and leaving along `wo`. Zero for delta lobes,
+    /// which only [`Material::sample`] can produce.
+    pub fn eval(&self, wo: Vector3, wi: Vector3, hit: &Hit) -> Vector3 {
+        match self {
+            Material::Lambertian { albedo } => lambertian::eval(albedo.value(hit), wi, hit),
+            Material::Metal {
+                albedo,
+                fuzz_radius,
+            } => metal::eval(albedo.value(hit), *fuzz_radius, wo, wi, hit),
+            Material::Dielectric {
+                refraction_index: _,
+            } => Vector3::ZERO,
+            Material::DiffuseLight { emit: _ } => Vector3::ZERO,
+            Material::UVGradient { intensity: _ } => Vector3::ZERO,
+        }
+    }
+
+    /// Draws an incoming direction for light leaving along `wo`, or `None` if the path is
+    /// absorbed.
+    pub fn sample<R: Rng + ?Sized>(
+        &self,
+        wo: Vector3,
+        hit: &Hit,
+        rng: &mut R,
+    ) -> Option<BsdfSample> {
+        match self {
+            Material::Lambertian { albedo } => lambertian::sample(albedo.value(hit), hit, rng),
             Material::Metal {
                 albedo,
                 fuzz_radius,
-            } => metal::scatter(albedo, fuzz_radius, ray, hit),
+            } => metal::sample(albedo.value(hit), *fuzz_radius, wo, hit, rng),
             Material::Dielectric { refraction_index } => {
-                dielectric::scatter(refraction_index, ray, hit)
+                Some(dielectric::sample(*refraction_index, wo, hit, rng))
             }
             Material::DiffuseLight { emit: _ } => None,
             Material::UVGradient { intensity: _ } => None,
         }
     }
 
+    /// Density, with respect to solid angle, of [`Material::sample`] drawing `wi`. Zero for
+    /// delta lobes.
+    pub fn pdf(&self, wo: Vector3, wi: Vector3, hit: &Hit) -> f64 {
+        match self {
+            Material::Lambertian { albedo: _ } => lambertian::pdf(wi, hit),
+            Material::Metal {
+                albedo: _,
+                fuzz_radius,
+            } => metal::pdf(*fuzz_radius, wo, wi, hit),
+            Material::Dielectric {
+                refraction_index: _,
+            } => 0.0,
+            Material::DiffuseLight { emit: _ } => 0.0,
+            Material::UVGradient { intensity: _ } => 0.0,
+        }
+    }
+
+    /// Whether every direction [`Material::sample`] draws is a delta lobe.
+    pub fn is_delta(&self) -> bool {
+        match self {
+            Material::Lambertian { albedo: _ } => false,
+            Material::Metal {
+                albedo: _,
+                fuzz_radius,
+            } => *fuzz_radius <= 0.0,
+            Material::Dielectric {
+                refraction_index: _,
+            } => true,
+            Material::DiffuseLight { emit: _ } => false,
+            Material::UVGradient { intensity: _ } => false,
+        }
+    }
+
     pub fn emitted(&self, _ray: &Ray, hit: &Hit) -> Vector3 {
-        match *self {
+        match self {
             Material::Lambertian { albedo: _ } => Vector3::ZERO,
             Material::Metal {
                 albedo: _,
@@ -42,91 +121,208 @@ impl Material {
             Material::Dielectric {
                 refraction_index: _,
             } => Vector3::ZERO,
-            Material::DiffuseLight { emit } => emit,
+            Material::DiffuseLight { emit } => emit.value(hit),
             Material::UVGradient { intensity } => {
                 let r = 1.0 - ((0.0 - hit.alpha).powi(2) + (0.0 - hit.beta).powi(2)).sqrt();
                 let g = 1.0 - ((1.0 - hit.alpha).powi(2) + (0.0 - hit.beta).powi(2)).sqrt();
                 let b = 1.0 - ((0.0 - hit.alpha).powi(2) + (1.0 - hit.beta).powi(2)).sqrt();
 
-                Vector3::new(r, g, b) * intensity
+                Vector3::new(r, g, b) * *intensity
             }
         }
     }
 }
 
+/// Whether `wi` points out of the side of the surface that was hit, going by both the
+/// shading and the true normal. Interpolated normals can tilt a reflection that looks
+/// fine for shading into the surface itself, so those are absorbed instead.
+fn reflects(wi: Vector3, hit: &Hit) -> bool {
+    dot(wi, hit.shading_normal) > 0.0 && dot(wi, hit.face_normal) > 0.0
+}
+
+/// Fills in a [`BsdfSample`] for a delta lobe picked with probability `pdf`, whose
+/// throughput weight is `weight`.
+fn delta_sample(wi: Vector3, weight: Vector3, pdf: f64, hit: &Hit) -> BsdfSample {
+    let cosine = dot(wi.to_unit(), hit.shading_normal).abs();
+
+    BsdfSample {
+        wi,
+        f: weight * (pdf / cosine),
+        pdf,
+        weight,
+        delta: true,
+    }
+}
+
 mod lambertian {
-    use super::Scatter;
-    use crate::{geometry::Hit, ray::Ray, vector::Vector3};
+    use std::f64::consts::PI;
+
+    use rand::Rng;
+
+    use super::{BsdfSample, reflects};
+    use crate::{
+        geometry::Hit,
+        sampling,
+        vector::{ONB, Vector3, dot},
+    };
 
-    pub fn scatter(albedo: Vector3, _ray: &Ray, hit: &Hit) -> Option<Scatter> {
-        let direction = hit.face_normal + Vector3::random_unit();
-        let direction = if direction.is_near_zero() {
-            hit.face_normal
+    pub fn eval(albedo: Vector3, wi: Vector3, hit: &Hit) -> Vector3 {
+        if reflects(wi, hit) {
+            albedo / PI
         } else {
-            direction
-        };
+            Vector3::ZERO
+        }
+    }
 
-        Some(Scatter {
-            ray: Ray::new(hit.p, direction),
-            attenuation: albedo,
+    pub fn sample<R: Rng + ?Sized>(albedo: Vector3, hit: &Hit, rng: &mut R) -> Option<BsdfSample> {
+        let local = sampling::cosine_hemisphere(rng);
+        let wi = ONB::from_w(hit.shading_normal).to_world(local);
+        if !reflects(wi, hit) {
+            return None;
+        }
+
+        Some(BsdfSample {
+            wi,
+            f: albedo / PI,
+            pdf: sampling::cosine_hemisphere_pdf(local.z),
+            weight: albedo,
+            delta: false,
         })
     }
+
+    pub fn pdf(wi: Vector3, hit: &Hit) -> f64 {
+        sampling::cosine_hemisphere_pdf(dot(wi.to_unit(), hit.shading_normal))
+    }
 }
 
 mod metal {
-    use super::Scatter;
+    use std::f64::consts::PI;
+
+    use rand::Rng;
+
+    use super::{BsdfSample, delta_sample, reflects};
     use crate::{
         geometry::Hit,
-        ray::Ray,
+        sampling,
         vector::{Vector3, dot, reflect},
     };
 
-    pub fn scatter(albedo: Vector3, fuzz_radius: f64, ray: &Ray, hit: &Hit) -> Option<Scatter> {
-        let reflected = reflect(ray.direction, hit.face_normal);
-        let fuzz = Vector3::random_unit() * fuzz_radius;
-        let fuzzed = reflected.to_unit() + fuzz;
-        if dot(fuzzed, hit.face_normal) > 0.0 {
-            Some(Scatter {
-                ray: Ray::new(hit.p, fuzzed),
-                attenuation: albedo,
-            })
-        } else {
-            None
+    pub fn eval(albedo: Vector3, fuzz_radius: f64, wo: Vector3, wi: Vector3, hit: &Hit) -> Vector3 {
+        if !reflects(wi, hit) {
+            return Vector3::ZERO;
+        }
+        let cosine = dot(wi.to_unit(), hit.shading_normal);
+
+        albedo * (pdf(fuzz_radius, wo, wi, hit) / cosine)
+    }
+
+    pub fn sample<R: Rng + ?Sized>(
+        albedo: Vector3,
+        fuzz_radius: f64,
+        wo: Vector3,
+        hit: &Hit,
+        rng: &mut R,
+    ) -> Option<BsdfSample> {
+        let reflected = reflect(-wo, hit.shading_normal).to_unit();
+        if fuzz_radius <= 0.0 {
+            if dot(reflected, hit.face_normal) <= 0.0 {
+                return None;
+            }
+            return Some(delta_sample(reflected, albedo, 1.0, hit));
+        }
+
+        let fuzz = sampling::uniform_sphere(rng) * fuzz_radius;
+        let wi = reflected + fuzz;
+        if !reflects(wi, hit) {
+            return None;
         }
+
+        let pdf = pdf(fuzz_radius, wo, wi, hit);
+        Some(BsdfSample {
+            wi,
+            f: albedo * (pdf / dot(wi.to_unit(), hit.shading_normal)),
+            pdf,
+            weight: albedo,
+            delta: false,
+        })
+    }
+
+    /// Density of `reflected + fuzz_radius * uniform_sphere`, normalized, pointing along `wi`.
+    /// The fuzzed endpoints lie uniformly on a sphere around the mirror direction, so this
+    /// sums that sphere's area density over where `wi` crosses it. Directions fuzzed below
+    /// the surface are absorbed and don't count toward it.
+    pub fn pdf(fuzz_radius: f64, wo: Vector3, wi: Vector3, hit: &Hit) -> f64 {
+        let wi = wi.to_unit();
+        if fuzz_radius <= 0.0 || !reflects(wi, hit) {
+            return 0.0;
+        }
+
+        let reflected = reflect(-wo, hit.shading_normal).to_unit();
+        let cos_theta = dot(wi, reflected);
+        let sin_squared = 1.0 - cos_theta.powi(2);
+        let discriminant = fuzz_radius.powi(2) - sin_squared;
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+
+        // distances along `wi` to the sphere, each weighted by how obliquely `wi` crosses it
+        let half_chord = discriminant.sqrt();
+        let near = cos_theta - half_chord;
+        let far = cos_theta + half_chord;
+        let crossings = if fuzz_radius < 1.0 {
+            if cos_theta <= 0.0 {
+                return 0.0;
+            }
+            near.powi(2) + far.powi(2)
+        } else {
+            // the sphere contains the hit point, so only the far side lies ahead
+            far.powi(2)
+        };
+
+        crossings / (4.0 * PI * fuzz_radius * half_chord)
     }
 }
 
 mod dielectric {
-    use super::{Scatter, reflectance};
+    use super::{BsdfSample, delta_sample, reflectance};
     use crate::{
         geometry::Hit,
-        ray::Ray,
         vector::{Vector3, dot, reflect, refract},
     };
-    use rand::random;
+    use rand::Rng;
 
-    pub fn scatter(refraction_index: f64, ray: &Ray, hit: &Hit) -> Option<Scatter> {
-        let r_in = ray.direction.to_unit();
+    pub fn sample<R: Rng + ?Sized>(
+        refraction_index: f64,
+        wo: Vector3,
+        hit: &Hit,
+        rng: &mut R,
+    ) -> BsdfSample {
+        let r_in = -wo.to_unit();
         let eta_in_over_eta_out = if hit.front_face {
             1.0 / refraction_index
         } else {
             refraction_index
         };
 
-        let cos_theta = dot(-r_in, hit.face_normal).clamp(-1.0, 1.0);
+        let normal = hit.shading_normal;
+        let cos_theta = dot(-r_in, normal).clamp(-1.0, 1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
-        let r_out = if eta_in_over_eta_out * sin_theta > 1.0
-            || reflectance(cos_theta, eta_in_over_eta_out) > random::<f64>()
-        {
-            reflect(r_in, hit.face_normal)
-        } else {
-            refract(r_in, hit.face_normal, eta_in_over_eta_out)
-        };
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        if eta_in_over_eta_out * sin_theta > 1.0 {
+            return delta_sample(reflect(r_in, normal), white, 1.0, hit);
+        }
 
-        Some(Scatter {
-            ray: Ray::new(hit.p, r_out),
-            attenuation: Vector3::new(1.0, 1.0, 1.0),
-        })
+        let reflect_probability = reflectance(cos_theta, eta_in_over_eta_out);
+        if reflect_probability > rng.random::<f64>() {
+            delta_sample(reflect(r_in, normal), white, reflect_probability, hit)
+        } else {
+            delta_sample(
+                refract(r_in, normal, eta_in_over_eta_out),
+                white,
+                1.0 - reflect_probability,
+                hit,
+            )
+        }
     }
 }
 
@@ -0,0 +1,303 @@
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::{
+    aabb::AABB,
+    geometry::{Geometry, Hit, mesh_triangle, vertex_normal},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    surface::{Hittable, Surface},
+    vector::Vector3,
+};
+
+/// An indexed triangle mesh. Vertex data is stored once and shared by every triangle
+/// that uses it; wrap the mesh in an [`Arc`] and use [`MeshTriangle::all`] to get one
+/// lightweight primitive per triangle.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TriangleMesh {
+    positions: Box<[Vector3]>,
+    /// counterclockwise (seen from the front) vertex indices of each triangle
+    indices: Box<[[u32; 3]]>,
+    /// optional per-vertex unit normals for smooth shading, parallel to `positions`
+    normals: Option<Box<[Vector3]>>,
+    /// optional per-vertex texture coordinates, parallel to `positions`
+    uvs: Option<Box<[(f64, f64)]>>,
+}
+
+#[derive(Error, Debug)]
+pub enum ConstructMeshError {
+    #[error("triangle {triangle} refers to vertex {index}, but there are only {vertices}")]
+    IndexOutOfBounds {
+        triangle: usize,
+        index: u32,
+        vertices: usize,
+    },
+    #[error("{attribute} has {len} entries (expected one per vertex, {vertices})")]
+    AttributeLength {
+        attribute: &'static str,
+        len: usize,
+        vertices: usize,
+    },
+    #[error("{0} triangles is more than a mesh can index")]
+    TooManyTriangles(usize),
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Box<[Vector3]>,
+        indices: Box<[[u32; 3]]>,
+        normals: Option<Box<[Vector3]>>,
+        uvs: Option<Box<[(f64, f64)]>>,
+    ) -> Result<Self, ConstructMeshError> {
+        let vertices = positions.len();
+
+        for (attribute, len) in [
+            ("normals", normals.as_ref().map(|normals| normals.len())),
+            ("uvs", uvs.as_ref().map(|uvs| uvs.len())),
+        ] {
+            if let Some(len) = len
+                && len != vertices
+            {
+                return Err(ConstructMeshError::AttributeLength {
+                    attribute,
+                    len,
+                    vertices,
+                });
+            }
+        }
+
+        if u32::try_from(indices.len()).is_err() {
+            return Err(ConstructMeshError::TooManyTriangles(indices.len()));
+        }
+
+        for (triangle, face) in indices.iter().enumerate() {
+            if let Some(&index) = face.iter().find(|&&index| index as usize >= vertices) {
+                return Err(ConstructMeshError::IndexOutOfBounds {
+                    triangle,
+                    index,
+                    vertices,
+                });
+            }
+        }
+
+        Ok(Self {
+            positions,
+            indices,
+            // interpolation weighs every corner the same only if they're all unit length
+            normals: normals.map(|normals| normals.iter().copied().map(vertex_normal).collect()),
+            uvs,
+        })
+    }
+
+    pub fn positions(&self) -> &[Vector3] {
+        &self.positions
+    }
+
+    pub fn indices(&self) -> &[[u32; 3]] {
+        &self.indices
+    }
+
+    pub fn normals(&self) -> Option<&[Vector3]> {
+        self.normals.as_deref()
+    }
+
+    pub fn uvs(&self) -> Option<&[(f64, f64)]> {
+        self.uvs.as_deref()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// The corners of triangle `index`, in winding order.
+    pub fn vertices(&self, index: usize) -> [Vector3; 3] {
+        self.indices[index].map(|i| self.positions[i as usize])
+    }
+
+    /// The normals at the corners of triangle `index`, if the mesh has any.
+    pub fn vertex_normals(&self, index: usize) -> Option<[Vector3; 3]> {
+        let normals = self.normals.as_ref()?;
+        Some(self.indices[index].map(|i| normals[i as usize]))
+    }
+
+    /// Triangle `index` as standalone geometry, smooth-shaded if the mesh has normals. Its
+    /// texture coordinates are its own barycentric ones, not the mesh's.
+    pub fn triangle(&self, index: usize) -> Geometry {
+        let [q, b, c] = self.vertices(index);
+        match self.vertex_normals(index) {
+            Some(normals) => Geometry::smooth_triangle(q, b - q, c - q, normals),
+            None => Geometry::triangle(q, b - q, c - q),
+        }
+    }
+
+    /// The texture coordinates at the corners of triangle `index`, if the mesh has any.
+    pub fn vertex_uvs(&self, index: usize) -> Option<[(f64, f64); 3]> {
+        let uvs = self.uvs.as_ref()?;
+        Some(self.indices[index].map(|i| uvs[i as usize]))
+    }
+
+    pub fn bounding_box(&self) -> AABB {
+        self.positions
+            .iter()
+            .map(|&p| AABB::new(p, p))
+            .fold(AABB::EMPTY, AABB::merge)
+    }
+}
+
+/// One triangle of a shared [`TriangleMesh`], made of a material shared with the rest of
+/// the mesh. It only holds references to both, so a BVH over millions of them stays small.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    material: Arc<Material>,
+    index: u32,
+}
+
+impl MeshTriangle {
+    /// Triangle `index` of `mesh`, made of `material`, or `None` if there's no such
+    /// triangle.
+    pub fn new(mesh: Arc<TriangleMesh>, material: Arc<Material>, index: usize) -> Option<Self> {
+        (index < mesh.triangle_count()).then_some(Self {
+            mesh,
+            material,
+            // the mesh can't have more triangles than a u32 counts
+            index: index as u32,
+        })
+    }
+
+    /// One primitive per triangle in `mesh`, all made of `material`.
+    pub fn all(mesh: Arc<TriangleMesh>, material: Arc<Material>) -> impl Iterator<Item = Self> {
+        // the mesh can't have more triangles than a u32 counts
+        (0..mesh.triangle_count() as u32).map(move |index| Self {
+            mesh: Arc::clone(&mesh),
+            material: Arc::clone(&material),
+            index,
+        })
+    }
+
+    pub fn mesh(&self) -> &Arc<TriangleMesh> {
+        &self.mesh
+    }
+
+    pub fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    /// Which of the mesh's triangles this is.
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<(Hit, Material)> {
+        let hit = mesh_triangle::hit(&self.mesh, self.index(), ray, ray_t)?;
+        Some((hit, Material::clone(&self.material)))
+    }
+
+    fn bounding_box(&self) -> AABB {
+        mesh_triangle::bounding_box(&self.mesh, self.index())
+    }
+
+    fn emitters(&self) -> Vec<Surface> {
+        match *self.material {
+            Material::DiffuseLight { .. } => vec![Surface::new(
+                self.mesh.triangle(self.index()),
+                Material::clone(&self.material),
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_square() -> (Box<[Vector3]>, Box<[[u32; 3]]>) {
+        let positions = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        (positions.into(), [[0, 1, 2], [0, 2, 3]].into())
+    }
+
+    #[test]
+    fn test_triangles_share_vertices() {
+        let (positions, indices) = unit_square();
+        let mesh = TriangleMesh::new(positions, indices, None, None).unwrap();
+
+        assert_eq!(mesh.vertices(1)[0], mesh.vertices(0)[0]);
+        assert_eq!(mesh.vertices(1)[1], mesh.vertices(0)[2]);
+        let material = Arc::new(Material::Lambertian {
+            albedo: Vector3::new(0.5, 0.5, 0.5).into(),
+        });
+        let triangles = MeshTriangle::all(Arc::new(mesh), material).collect::<Vec<_>>();
+        assert_eq!(triangles.len(), 2);
+        assert!(Arc::ptr_eq(triangles[0].mesh(), triangles[1].mesh()));
+        assert!(Arc::ptr_eq(
+            triangles[0].material(),
+            triangles[1].material()
+        ));
+    }
+
+    #[test]
+    fn test_mesh_triangles_are_small() {
+        // two pointers and an index, where a Surface holding a whole Triangle and
+        // Material runs to a couple of hundred bytes
+        assert!(size_of::<MeshTriangle>() <= 3 * size_of::<usize>());
+        assert!(size_of::<MeshTriangle>() * 4 < size_of::<Surface>());
+    }
+
+    #[test]
+    fn test_standalone_triangle_matches() {
+        let (positions, indices) = unit_square();
+        let normals = [Vector3::new(0.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 1.0)].repeat(2);
+        let mesh = TriangleMesh::new(positions, indices, Some(normals.into()), None).unwrap();
+        let material = Arc::new(Material::DiffuseLight {
+            emit: Vector3::new(1.0, 1.0, 1.0).into(),
+        });
+
+        let ray = Ray::new(Vector3::new(0.7, 0.5, 1.0), Vector3::new(0.1, 0.0, -1.0));
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+        for triangle in MeshTriangle::all(Arc::new(mesh), material) {
+            let [emitter] = triangle.emitters().try_into().unwrap();
+            assert_eq!(emitter.geometry, triangle.mesh().triangle(triangle.index()));
+            assert_eq!(emitter.bounding_box(), triangle.bounding_box());
+
+            match (triangle.hit(&ray, &ray_t), emitter.hit(&ray, &ray_t)) {
+                (Some((expected, _)), Some((actual, _))) => {
+                    assert!((actual.t - expected.t).abs() < 1e-12);
+                    assert!((actual.shading_normal - expected.shading_normal).length() < 1e-12);
+                }
+                (expected, actual) => assert_eq!(expected.is_none(), actual.is_none()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_indices_and_attributes() {
+        let (positions, _) = unit_square();
+        assert!(matches!(
+            TriangleMesh::new(positions.clone(), [[0, 1, 4]].into(), None, None),
+            Err(ConstructMeshError::IndexOutOfBounds {
+                triangle: 0,
+                index: 4,
+                vertices: 4
+            })
+        ));
+
+        let (positions, indices) = unit_square();
+        assert!(matches!(
+            TriangleMesh::new(positions, indices, None, Some([(0.0, 0.0)].into())),
+            Err(ConstructMeshError::AttributeLength {
+                attribute: "uvs",
+                ..
+            })
+        ));
+    }
+}
@@ -1,42 +1,157 @@
 use std::error::Error;
-use std::time::Instant;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::bvh::{BVH, PartitionBy, SAHBucketStrategy};
+use crate::bvh::{BVH, PartitionBy, SAHBucketStrategy, SAHLeafPolicy, TopLevelBVH};
 use crate::camera::Camera;
-use crate::surface::Surface;
+use crate::color::DisplayTransform;
+use crate::image::{Image, ImageFormat, SaveImageError, ppm};
+use crate::instance::Instance;
+use crate::light::Lights;
+use crate::surface::{Hittable, Surface};
+use crate::vector::Transform;
 
 pub struct RenderRunner {
     pub camera: Camera,
     pub partition_strategy: PartitionBy,
+
+    /// Where to save the rendered image, in the format implied by the file extension.
+    pub output: Option<PathBuf>,
+
+    /// Tone mapping and encoding applied when `output` is a low dynamic range format.
+    pub display_transform: DisplayTransform,
+}
+
+/// The output of a [`RenderRunner`]: the linear framebuffer and how long it took to produce.
+pub struct Render {
+    pub image: Image,
+    pub timings: RenderTimings,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RenderTimings {
+    pub total: Duration,
+    pub bvh_construction: Duration,
+    pub rendering: Duration,
+}
+
+impl fmt::Display for RenderTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Total runtime: {:#?}\nBVH construction: {:#?}\nRendering: {:#?}",
+            self.total, self.bvh_construction, self.rendering
+        )
+    }
 }
 
 impl Default for RenderRunner {
     fn default() -> Self {
         Self {
             camera: Camera::default(),
-            partition_strategy: PartitionBy::SurfaceAreaHeuristic(SAHBucketStrategy::PerSurface),
+            partition_strategy: PartitionBy::SurfaceAreaHeuristic(
+                SAHBucketStrategy::PerSurface,
+                SAHLeafPolicy::default(),
+            ),
+            output: None,
+            display_transform: DisplayTransform::default(),
         }
     }
 }
 
 impl RenderRunner {
-    pub fn run(self, surfaces: Box<[Surface]>) -> Result<(), Box<dyn Error>> {
+    pub fn run(self, surfaces: Box<[Surface]>) -> Result<Render, Box<dyn Error>> {
         let start_time = Instant::now();
 
+        let lights = Lights::from_surfaces(&surfaces);
+
         let bvh_start_time = Instant::now();
         let world = BVH::from_slice(surfaces, &self.partition_strategy);
-        let bvh_time = bvh_start_time.elapsed();
+        let bvh_construction = bvh_start_time.elapsed();
 
-        let render_start_time = Instant::now();
-        self.camera.initialize().render(&world);
-        let render_time = render_start_time.elapsed();
+        self.render(&world, &lights, start_time, bvh_construction)
+    }
 
-        let total_time = start_time.elapsed();
+    /// Renders `surfaces` alongside `instances` of shared objects, with a [`TopLevelBVH`]
+    /// over both. Lights are gathered from both, too.
+    pub fn run_with_instances(
+        self,
+        surfaces: Box<[Surface]>,
+        instances: Box<[Instance<dyn Hittable>]>,
+    ) -> Result<Render, Box<dyn Error>> {
+        let start_time = Instant::now();
 
-        eprintln!(
-            "\n\nDone!\nTotal runtime: {total_time:#?}\nBVH construction: {bvh_time:#?}\nRendering: {render_time:#?}",
-        );
+        let bvh_start_time = Instant::now();
+        let surfaces: Arc<dyn Hittable> =
+            Arc::new(BVH::from_slice(surfaces, &self.partition_strategy));
+        // the loose surfaces are just one more object, placed as-is
+        let mut instances = Vec::from(instances);
+        instances.push(Instance::new(surfaces, Transform::IDENTITY));
+        let world = TopLevelBVH::from_slice(instances.into(), &self.partition_strategy);
+        let bvh_construction = bvh_start_time.elapsed();
+
+        let lights = Lights::from_world(&world);
+        self.render(&world, &lights, start_time, bvh_construction)
+    }
+
+    /// Renders with `run`, usually [`RenderRunner::run`] or [`RenderRunner::run_with_instances`],
+    /// the way a command line program would: saved to `output` if there is one, or written to
+    /// stdout as a plain-text PPM otherwise. Timings go to stderr, so stdout only holds the
+    /// image.
+    pub fn run_cli(
+        self,
+        output: Option<PathBuf>,
+        run: impl FnOnce(Self) -> Result<Render, Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let to_stdout = output.is_none();
+        let display_transform = self.display_transform;
+        let render = run(Self { output, ..self })?;
+        eprintln!("{}", render.timings);
+
+        if to_stdout {
+            ppm::write(&mut io::stdout().lock(), &render.image, &display_transform)?;
+        }
 
         Ok(())
     }
+
+    fn render(
+        self,
+        world: &impl Hittable,
+        lights: &Lights,
+        start_time: Instant,
+        bvh_construction: Duration,
+    ) -> Result<Render, Box<dyn Error>> {
+        // catch a bad output path or display transform before spending the whole render on
+        // them
+        self.display_transform.tone_mapper.validate()?;
+        self.display_transform.transfer.validate()?;
+        let output = match &self.output {
+            Some(path) => {
+                let format = ImageFormat::from_path(path)
+                    .ok_or_else(|| SaveImageError::UnknownFormat(path.as_path().into()))?;
+                Some((path, format))
+            }
+            None => None,
+        };
+
+        let render_start_time = Instant::now();
+        let image = self.camera.initialize().render(world, lights);
+        let rendering = render_start_time.elapsed();
+
+        let timings = RenderTimings {
+            total: start_time.elapsed(),
+            bvh_construction,
+            rendering,
+        };
+
+        if let Some((path, format)) = output {
+            image.save_as(path, format, &self.display_transform)?;
+        }
+
+        Ok(Render { image, timings })
+    }
 }
@@ -0,0 +1,148 @@
+//! Warps uniform random numbers into the distributions the renderer samples from.
+//!
+//! Directions are returned in local coordinates, with `z` as the axis of symmetry: use an
+//! [`ONB`](crate::vector::ONB) to bring them into world space. Each sampler comes with the
+//! density it draws by, with respect to solid angle for directions and area for points.
+
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use rand::Rng;
+
+use crate::vector::Vector3;
+
+/// A direction on the `z > 0` hemisphere, with density proportional to its cosine.
+pub fn cosine_hemisphere<R: Rng + ?Sized>(rng: &mut R) -> Vector3 {
+    // Malley's method: project a uniform disk up onto the hemisphere
+    let d = concentric_disk(rng);
+    let z = (1.0 - d.x * d.x - d.y * d.y).max(0.0).sqrt();
+
+    Vector3::new(d.x, d.y, z)
+}
+
+pub fn cosine_hemisphere_pdf(cos_theta: f64) -> f64 {
+    cos_theta.max(0.0) / PI
+}
+
+/// A direction uniformly distributed over the unit sphere.
+pub fn uniform_sphere<R: Rng + ?Sized>(rng: &mut R) -> Vector3 {
+    let z = 1.0 - 2.0 * rng.random::<f64>();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.random::<f64>();
+
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_sphere_pdf() -> f64 {
+    1.0 / (4.0 * PI)
+}
+
+/// A direction uniformly distributed over the cone around `z` with half-angle `theta_max`.
+///
+/// The cone is given by `1 - cos(theta_max)` rather than the angle itself, since that's
+/// what stays precise for very narrow cones.
+pub fn uniform_cone<R: Rng + ?Sized>(one_minus_cos_max: f64, rng: &mut R) -> Vector3 {
+    let one_minus_cos = rng.random::<f64>() * one_minus_cos_max;
+    let cos_theta = 1.0 - one_minus_cos;
+    // sin^2 = 1 - cos^2, factored the same way
+    let sin_theta = (one_minus_cos * (2.0 - one_minus_cos)).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.random::<f64>();
+
+    Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+pub fn uniform_cone_pdf(one_minus_cos_max: f64) -> f64 {
+    1.0 / (2.0 * PI * one_minus_cos_max)
+}
+
+/// A point uniformly distributed over the unit disk in the `z = 0` plane.
+///
+/// Shirley and Chiu's concentric mapping, which keeps neighbouring samples neighbours
+/// unlike the polar mapping, and needs no rejection loop.
+pub fn concentric_disk<R: Rng + ?Sized>(rng: &mut R) -> Vector3 {
+    let a = 2.0 * rng.random::<f64>() - 1.0;
+    let b = 2.0 * rng.random::<f64>() - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return Vector3::ZERO;
+    }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * (b / a))
+    } else {
+        (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+    };
+
+    Vector3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+pub fn concentric_disk_pdf() -> f64 {
+    1.0 / PI
+}
+
+/// Barycentric coordinates `(b1, b2)` of a point uniformly distributed over a triangle,
+/// weighting its second and third vertices respectively.
+pub fn uniform_triangle<R: Rng + ?Sized>(rng: &mut R) -> (f64, f64) {
+    // fold samples from the far half of the unit square back into the triangle
+    let (a, b) = (rng.random::<f64>(), rng.random::<f64>());
+    if a + b > 1.0 {
+        (1.0 - a, 1.0 - b)
+    } else {
+        (a, b)
+    }
+}
+
+pub fn uniform_triangle_pdf(area: f64) -> f64 {
+    1.0 / area
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::vector::Vector3;
+
+    const SAMPLES: u32 = 100_000;
+
+    /// Monte Carlo estimate of the integral of `f` over the sphere, by `uniform_sphere`.
+    fn integrate_sphere(f: impl Fn(Vector3) -> f64) -> f64 {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let sum: f64 = (0..SAMPLES)
+            .map(|_| f(uniform_sphere(&mut rng)) / uniform_sphere_pdf())
+            .sum();
+        sum / f64::from(SAMPLES)
+    }
+
+    #[test]
+    fn test_direction_pdfs_integrate_to_one() {
+        let cosine = integrate_sphere(|d| cosine_hemisphere_pdf(d.z));
+        assert!((cosine - 1.0).abs() < 0.01, "{cosine}");
+
+        let extent = 0.3;
+        let cone = integrate_sphere(|d| {
+            if 1.0 - d.z <= extent {
+                uniform_cone_pdf(extent)
+            } else {
+                0.0
+            }
+        });
+        assert!((cone - 1.0).abs() < 0.02, "{cone}");
+    }
+
+    #[test]
+    fn test_samples_land_where_their_pdfs_are_defined() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..SAMPLES {
+            let d = cosine_hemisphere(&mut rng);
+            assert!(d.z >= 0.0 && (d.length() - 1.0).abs() < 1e-9);
+
+            let d = uniform_cone(0.3, &mut rng);
+            assert!(1.0 - d.z <= 0.3 + 1e-12 && (d.length() - 1.0).abs() < 1e-9);
+
+            assert!(concentric_disk(&mut rng).length() <= 1.0);
+
+            let (b1, b2) = uniform_triangle(&mut rng);
+            assert!(b1 >= 0.0 && b2 >= 0.0 && b1 + b2 <= 1.0);
+        }
+    }
+}
@@ -9,6 +9,9 @@ use crate::{
 pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<(Hit, Material)>;
     fn bounding_box(&self) -> AABB;
+    /// Standalone copies, in the same space as `hit`, of the emissive surfaces within,
+    /// for [`Lights`](crate::light::Lights) to aim at.
+    fn emitters(&self) -> Vec<Surface>;
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -35,9 +38,16 @@ impl Hittable for Surface {
     fn bounding_box(&self) -> AABB {
         self.geometry.bounding_box()
     }
+
+    fn emitters(&self) -> Vec<Surface> {
+        match self.material {
+            Material::DiffuseLight { .. } => vec![self.clone()],
+            _ => Vec::new(),
+        }
+    }
 }
 
-impl Hittable for &[Surface] {
+impl<T: Hittable> Hittable for &[T] {
     fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<(Hit, Material)> {
         self.iter().fold(None, |acc, e| {
             let maybe_hit = e.hit(ray, ray_t);
@@ -56,4 +66,8 @@ impl Hittable for &[Surface] {
             .map(|e| e.bounding_box())
             .fold(AABB::EMPTY, AABB::merge)
     }
+
+    fn emitters(&self) -> Vec<Surface> {
+        self.iter().flat_map(|e| e.emitters()).collect()
+    }
 }
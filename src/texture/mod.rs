@@ -0,0 +1,251 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{
+    color::TransferFunction,
+    geometry::Hit,
+    image::{Image, ReadImageError},
+    vector::Vector3,
+};
+
+pub mod perlin;
+
+pub use perlin::Perlin;
+
+/// How texture coordinates outside [0, 1] map back onto an image.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum WrapMode {
+    /// Tile the image.
+    #[default]
+    Repeat,
+    /// Tile the image, flipping every other copy so edges meet seamlessly.
+    Mirror,
+    /// Stretch the edge pixels outward.
+    Clamp,
+}
+
+/// A color that varies over a surface.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Texture {
+    Solid {
+        color: Vector3,
+    },
+    /// Looked up by the hit's `(alpha, beta)` surface coordinates, with `(0, 0)` at the
+    /// image's bottom-left corner, and filtered bilinearly.
+    Image {
+        image: Arc<Image>,
+        wrap: WrapMode,
+    },
+
+    /// Alternates between two textures in a 3D grid of cubes `scale` wide, so solids look
+    /// carved out of it regardless of how they're parameterized.
+    Checker {
+        scale: f64,
+        even: Box<Texture>,
+        odd: Box<Texture>,
+    },
+    /// Alternates between two textures in a grid over the surface coordinates.
+    UVChecker {
+        columns: u32,
+        rows: u32,
+        even: Box<Texture>,
+        odd: Box<Texture>,
+    },
+
+    /// Gray Perlin noise, with features roughly `1 / scale` across.
+    Noise {
+        perlin: Arc<Perlin>,
+        scale: f64,
+    },
+    /// Gray turbulence: several octaves of noise magnitude, giving a billowy look.
+    Turbulence {
+        perlin: Arc<Perlin>,
+        scale: f64,
+        octaves: u32,
+    },
+    /// Gray stripes along `z`, `scale` per unit, with turbulence bending them into veins.
+    Marble {
+        perlin: Arc<Perlin>,
+        scale: f64,
+        octaves: u32,
+    },
+    /// Growth rings around the `y` axis, blending from `light` to `dark` across each ring,
+    /// and warped by noise.
+    Wood {
+        perlin: Arc<Perlin>,
+        scale: f64,
+        light: Vector3,
+        dark: Vector3,
+    },
+}
+
+impl Texture {
+    /// Loads an image texture from a PPM or PNG file, treating its samples as sRGB
+    /// encoded.
+    pub fn open(path: impl AsRef<Path>, wrap: WrapMode) -> Result<Self, ReadImageError> {
+        let mut image = Image::open(path)?;
+        for pixel in image.pixels_mut() {
+            let decode = |encoded| TransferFunction::Srgb.decode(encoded);
+            *pixel = Vector3::new(decode(pixel.x), decode(pixel.y), decode(pixel.z));
+        }
+
+        Ok(Texture::Image {
+            image: Arc::new(image),
+            wrap,
+        })
+    }
+
+    pub fn value(&self, hit: &Hit) -> Vector3 {
+        match self {
+            Texture::Solid { color } => *color,
+            Texture::Image { image, wrap } => image::bilinear(image, *wrap, hit.alpha, hit.beta),
+
+            Texture::Checker { scale, even, odd } => {
+                if checker::is_even(hit.p / *scale) {
+                    even.value(hit)
+                } else {
+                    odd.value(hit)
+                }
+            }
+            Texture::UVChecker {
+                columns,
+                rows,
+                even,
+                odd,
+            } => {
+                let cell = Vector3::new(
+                    hit.alpha * f64::from(*columns),
+                    hit.beta * f64::from(*rows),
+                    0.0,
+                );
+                if checker::is_even(cell) {
+                    even.value(hit)
+                } else {
+                    odd.value(hit)
+                }
+            }
+
+            Texture::Noise { perlin, scale } => gray(0.5 * (1.0 + perlin.noise(hit.p * *scale))),
+            Texture::Turbulence {
+                perlin,
+                scale,
+                octaves,
+            } => gray(perlin.turbulence(hit.p * *scale, *octaves)),
+            Texture::Marble {
+                perlin,
+                scale,
+                octaves,
+            } => {
+                // the turbulence deliberately stays at its natural scale, so `scale` sets
+                // how tightly the veins are packed without making them any more ragged
+                let phase = *scale * hit.p.z + 10.0 * perlin.turbulence(hit.p, *octaves);
+                gray(0.5 * (1.0 + phase.sin()))
+            }
+            Texture::Wood {
+                perlin,
+                scale,
+                light,
+                dark,
+            } => {
+                let p = hit.p * *scale;
+                let radius = (p.x.powi(2) + p.z.powi(2)).sqrt();
+                let ring = (radius + 0.5 * perlin.noise(p)).rem_euclid(1.0);
+                *light * (1.0 - ring) + *dark * ring
+            }
+        }
+    }
+}
+
+fn gray(value: f64) -> Vector3 {
+    Vector3::new(value, value, value)
+}
+
+impl From<Vector3> for Texture {
+    fn from(color: Vector3) -> Self {
+        Texture::Solid { color }
+    }
+}
+
+mod checker {
+    use crate::vector::Vector3;
+
+    /// Whether `p` lies in an even cell of the unit grid.
+    pub fn is_even(p: Vector3) -> bool {
+        let sum = p.x.floor() as i64 + p.y.floor() as i64 + p.z.floor() as i64;
+        sum.rem_euclid(2) == 0
+    }
+}
+
+mod image {
+    use super::WrapMode;
+    use crate::{image::Image, vector::Vector3};
+
+    pub fn bilinear(image: &Image, wrap: WrapMode, u: f64, v: f64) -> Vector3 {
+        if image.width() == 0 || image.height() == 0 {
+            return Vector3::ZERO;
+        }
+
+        // continuous pixel coordinates, with pixel centers at integers plus a half
+        let x = u * f64::from(image.width()) - 0.5;
+        let y = (1.0 - v) * f64::from(image.height()) - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let texel = |dx: i64, dy: i64| {
+            let col = wrap_index(wrap, x0 as i64 + dx, image.width());
+            let row = wrap_index(wrap, y0 as i64 + dy, image.height());
+            image[(col, row)]
+        };
+
+        let top = texel(0, 0) * (1.0 - tx) + texel(1, 0) * tx;
+        let bottom = texel(0, 1) * (1.0 - tx) + texel(1, 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    pub fn wrap_index(wrap: WrapMode, i: i64, size: u32) -> u32 {
+        let size = i64::from(size);
+        let i = match wrap {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size { i } else { 2 * size - 1 - i }
+            }
+            WrapMode::Clamp => i.clamp(0, size - 1),
+        };
+
+        i as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_modes() {
+        let wrapped = |wrap| {
+            (-4..4)
+                .map(|i| image::wrap_index(wrap, i, 3))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(wrapped(WrapMode::Repeat), [2, 0, 1, 2, 0, 1, 2, 0]);
+        assert_eq!(wrapped(WrapMode::Mirror), [2, 2, 1, 0, 0, 1, 2, 2]);
+        assert_eq!(wrapped(WrapMode::Clamp), [0, 0, 0, 0, 0, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_bilinear_filtering() {
+        // one black and one white pixel, side by side
+        let mut image = Image::new(2, 1);
+        image[(1, 0)] = Vector3::new(1.0, 1.0, 1.0);
+        let lookup = |u| image::bilinear(&image, WrapMode::Clamp, u, 0.5).x;
+
+        // pixel centers are exact, and halfway between them blends evenly
+        assert_eq!(lookup(0.25), 0.0);
+        assert_eq!(lookup(0.75), 1.0);
+        assert_eq!(lookup(0.5), 0.5);
+        // clamping holds the edge pixel's color past the border
+        assert_eq!(lookup(-1.0), 0.0);
+    }
+}
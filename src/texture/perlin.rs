@@ -0,0 +1,137 @@
+//! Ken Perlin's improved gradient noise ("Improving Noise", SIGGRAPH 2002), with the
+//! permutation shuffled from a seed so different textures needn't share a pattern.
+
+use rand::{SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha8Rng;
+
+use crate::vector::Vector3;
+
+const SIZE: usize = 256;
+
+/// A seeded noise field. Build one once and share it between textures; it's a couple of
+/// kilobytes of tables.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Perlin {
+    /// A permutation of `0..SIZE`, stored twice so lookups never need to wrap.
+    permutation: Box<[u8]>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut permutation: Vec<u8> = (0..=u8::MAX).collect();
+        permutation.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+        permutation.extend_from_within(..);
+
+        Self {
+            permutation: permutation.into_boxed_slice(),
+        }
+    }
+
+    /// Smooth noise in roughly [-1, 1], zero at every integer lattice point.
+    pub fn noise(&self, p: Vector3) -> f64 {
+        let cell = [p.x.floor(), p.y.floor(), p.z.floor()];
+        let [x, y, z] = [p.x - cell[0], p.y - cell[1], p.z - cell[2]];
+        let [i, j, k] = cell.map(|c| (c as i64).rem_euclid(SIZE as i64) as usize);
+
+        let hash = |di: usize, dj: usize, dk: usize| {
+            let h = self.permutation[i + di];
+            let h = self.permutation[usize::from(h) + j + dj];
+            self.permutation[usize::from(h) + k + dk]
+        };
+        let corner = |di: usize, dj: usize, dk: usize| {
+            gradient(
+                hash(di, dj, dk),
+                x - di as f64,
+                y - dj as f64,
+                z - dk as f64,
+            )
+        };
+
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    /// Sum of `octaves` layers of noise magnitude, each at twice the frequency and half
+    /// the weight of the last.
+    pub fn turbulence(&self, p: Vector3, octaves: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            sum += weight * self.noise(p).abs();
+            weight *= 0.5;
+            p *= 2.0;
+        }
+
+        sum
+    }
+}
+
+/// `6t^5 - 15t^4 + 10t^3`, which has zero first and second derivatives at both ends.
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Dot product of `(x, y, z)` with one of the twelve cube-edge gradients, picked by `hash`.
+fn gradient(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..4 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_vanishes_on_the_lattice() {
+        let perlin = Perlin::new(0);
+        for p in [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(3.0, -7.0, 12.0),
+            Vector3::new(-300.0, 255.0, 256.0),
+        ] {
+            assert_eq!(perlin.noise(p), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_noise_is_seeded() {
+        let p = Vector3::new(0.3, 1.7, -2.2);
+
+        assert_eq!(Perlin::new(1).noise(p), Perlin::new(1).noise(p));
+        assert_ne!(Perlin::new(1).noise(p), Perlin::new(2).noise(p));
+    }
+
+    #[test]
+    fn test_noise_is_bounded() {
+        let perlin = Perlin::new(0);
+        for i in 0..10_000 {
+            let t = f64::from(i) * 0.0137;
+            let p = Vector3::new(t, 1.3 * t - 5.0, (0.7 * t).sin() * 20.0);
+            assert!(perlin.noise(p).abs() <= 1.1);
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use std::ops;
+use std::{array, ops};
 
-use rand::{random, random_range};
+use rand::Rng;
 
 use crate::interval::Interval;
 
@@ -36,29 +36,33 @@ impl Vector3 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
 
+    pub fn max_component(&self) -> f64 {
+        self.x.max(self.y).max(self.z)
+    }
+
     pub fn to_unit(self) -> Self {
         self / self.length()
     }
 
-    pub fn random_range(range: Interval) -> Self {
+    pub fn random_range<R: Rng + ?Sized>(range: Interval, rng: &mut R) -> Self {
         Self {
-            x: random_range(range.min..range.max),
-            y: random_range(range.min..range.max),
-            z: random_range(range.min..range.max),
+            x: rng.random_range(range.min..range.max),
+            y: rng.random_range(range.min..range.max),
+            z: rng.random_range(range.min..range.max),
         }
     }
 
-    pub fn random() -> Self {
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
         Self {
-            x: random::<f64>(),
-            y: random::<f64>(),
-            z: random::<f64>(),
+            x: rng.random::<f64>(),
+            y: rng.random::<f64>(),
+            z: rng.random::<f64>(),
         }
     }
 
-    pub fn random_unit() -> Self {
+    pub fn random_unit<R: Rng + ?Sized>(rng: &mut R) -> Self {
         loop {
-            let candidate = Self::random_range(Interval::new(-1.0, 1.0));
+            let candidate = Self::random_range(Interval::new(-1.0, 1.0), rng);
             let lensq = candidate.length_squared();
 
             // there exist candidate vectors s.t. candidate.length_squared() == 0.0
@@ -80,11 +84,11 @@ impl Vector3 {
         }
     }
 
-    pub fn random_in_unit_disk() -> Self {
+    pub fn random_in_unit_disk<R: Rng + ?Sized>(rng: &mut R) -> Self {
         loop {
             let candidate = Self {
-                x: random_range(-1.0..1.0),
-                y: random_range(-1.0..1.0),
+                x: rng.random_range(-1.0..1.0),
+                y: rng.random_range(-1.0..1.0),
                 z: 0.0,
             };
 
@@ -94,8 +98,8 @@ impl Vector3 {
         }
     }
 
-    pub fn random_on_hemisphere(normal: Self) -> Self {
-        let on_unit_sphere = Self::random_unit();
+    pub fn random_on_hemisphere<R: Rng + ?Sized>(normal: Self, rng: &mut R) -> Self {
+        let on_unit_sphere = Self::random_unit(rng);
         if dot(on_unit_sphere, normal) > 0.0 {
             on_unit_sphere
         } else {
@@ -262,3 +266,345 @@ pub fn refract(r_in: Vector3, n: Vector3, eta_in_over_eta_out: f64) -> Vector3 {
 
     r_out_perp + r_out_par
 }
+
+/// An orthonormal basis, built around `w`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ONB {
+    pub u: Vector3,
+    pub v: Vector3,
+    pub w: Vector3,
+}
+
+impl ONB {
+    /// Completes a basis around `w`, which needn't be normalized.
+    pub fn from_w(w: Vector3) -> Self {
+        let w = w.to_unit();
+
+        // Duff et al., "Building an Orthonormal Basis, Revisited": branchless and free of
+        // the precision cliffs of crossing with a fixed axis
+        let sign = 1.0_f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        let u = Vector3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x);
+        let v = Vector3::new(b, sign + w.y * w.y * a, -w.y);
+
+        Self { u, v, w }
+    }
+
+    /// Transforms `local`, given in this basis' coordinates, into world space.
+    pub fn to_world(&self, local: Vector3) -> Vector3 {
+        local.x * self.u + local.y * self.v + local.z * self.w
+    }
+
+    /// Transforms `world` into this basis' coordinates.
+    pub fn to_local(&self, world: Vector3) -> Vector3 {
+        Vector3::new(dot(world, self.u), dot(world, self.v), dot(world, self.w))
+    }
+}
+
+/// A 4x4 matrix acting on homogeneous coordinates, stored row by row.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Matrix4(pub [[f64; 4]; 4]);
+
+impl Matrix4 {
+    pub const IDENTITY: Self = Self([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub fn transpose(&self) -> Self {
+        Self(array::from_fn(|row| array::from_fn(|col| self.0[col][row])))
+    }
+
+    /// The inverse, by Gauss-Jordan elimination, or `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut m = self.0;
+        let mut inverse = Self::IDENTITY.0;
+
+        for col in 0..4 {
+            // partial pivoting keeps the elimination stable
+            let pivot = (col..4)
+                .max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))
+                .unwrap();
+            if m[pivot][col] == 0.0 {
+                return None;
+            }
+            m.swap(col, pivot);
+            inverse.swap(col, pivot);
+
+            let scale = 1.0 / m[col][col];
+            for k in 0..4 {
+                m[col][k] *= scale;
+                inverse[col][k] *= scale;
+            }
+
+            for row in (0..4).filter(|&row| row != col) {
+                let factor = m[row][col];
+                for k in 0..4 {
+                    m[row][k] -= factor * m[col][k];
+                    inverse[row][k] -= factor * inverse[col][k];
+                }
+            }
+        }
+
+        Some(Self(inverse))
+    }
+
+    /// Transforms `p` as a point, so translations apply.
+    pub fn transform_point(&self, p: Vector3) -> Vector3 {
+        let [r0, r1, r2, r3] = self.0;
+        let apply = |row: [f64; 4]| row[0] * p.x + row[1] * p.y + row[2] * p.z + row[3];
+
+        // affine transforms leave w at 1, but stay correct for anything projective
+        let w = apply(r3);
+        Vector3::new(apply(r0), apply(r1), apply(r2)) / w
+    }
+
+    /// Transforms `v` as a direction, so translations don't apply.
+    pub fn transform_vector(&self, v: Vector3) -> Vector3 {
+        let [r0, r1, r2, _] = self.0;
+        let apply = |row: [f64; 4]| row[0] * v.x + row[1] * v.y + row[2] * v.z;
+
+        Vector3::new(apply(r0), apply(r1), apply(r2))
+    }
+}
+
+impl ops::Mul for Matrix4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self(array::from_fn(|row| {
+            array::from_fn(|col| (0..4).map(|k| self.0[row][k] * rhs.0[k][col]).sum())
+        }))
+    }
+}
+
+/// An invertible affine transform, kept alongside its inverse so either direction is a
+/// single matrix multiply.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        matrix: Matrix4::IDENTITY,
+        inverse: Matrix4::IDENTITY,
+    };
+
+    /// Wraps `matrix`, or `None` if it can't be inverted.
+    pub fn from_matrix(matrix: Matrix4) -> Option<Self> {
+        Some(Self {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn translation(offset: Vector3) -> Self {
+        let translate = |offset: Vector3| {
+            Matrix4([
+                [1.0, 0.0, 0.0, offset.x],
+                [0.0, 1.0, 0.0, offset.y],
+                [0.0, 0.0, 1.0, offset.z],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+        };
+
+        Self {
+            matrix: translate(offset),
+            inverse: translate(-offset),
+        }
+    }
+
+    /// Rotates counterclockwise by `angle` radians about `axis` (through the origin), when
+    /// looking down `axis` toward the origin. `axis` needn't be normalized, but `None` if
+    /// it's zero, or the angle isn't finite.
+    pub fn rotation(axis: Vector3, angle: f64) -> Option<Self> {
+        let length_squared = axis.length_squared();
+        if !(length_squared > 0.0 && length_squared.is_finite() && angle.is_finite()) {
+            return None;
+        }
+
+        let Vector3 { x, y, z } = axis.to_unit();
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+
+        // Rodrigues' rotation formula
+        let matrix = Matrix4([
+            [
+                t * x * x + cos,
+                t * x * y - sin * z,
+                t * x * z + sin * y,
+                0.0,
+            ],
+            [
+                t * x * y + sin * z,
+                t * y * y + cos,
+                t * y * z - sin * x,
+                0.0,
+            ],
+            [
+                t * x * z - sin * y,
+                t * y * z + sin * x,
+                t * z * z + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        // rotations are orthogonal
+        Some(Self {
+            matrix,
+            inverse: matrix.transpose(),
+        })
+    }
+
+    /// Scales each axis by the matching component of `factors`, or `None` unless they're
+    /// all finite and nonzero.
+    pub fn scaling(factors: Vector3) -> Option<Self> {
+        let invertible = |factor: f64| factor != 0.0 && factor.is_finite();
+        if ![factors.x, factors.y, factors.z]
+            .into_iter()
+            .all(invertible)
+        {
+            return None;
+        }
+
+        let scale = |factors: Vector3| {
+            Matrix4([
+                [factors.x, 0.0, 0.0, 0.0],
+                [0.0, factors.y, 0.0, 0.0],
+                [0.0, 0.0, factors.z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+        };
+
+        Some(Self {
+            matrix: scale(factors),
+            inverse: scale(Vector3::new(
+                1.0 / factors.x,
+                1.0 / factors.y,
+                1.0 / factors.z,
+            )),
+        })
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(self, next: Transform) -> Self {
+        Self {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn inverse(self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4 {
+        self.matrix
+    }
+
+    pub fn point(&self, p: Vector3) -> Vector3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vector3) -> Vector3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Transforms a surface normal. Normals only stay perpendicular to their surface under
+    /// the inverse transpose, and come out unnormalized.
+    pub fn normal(&self, n: Vector3) -> Vector3 {
+        let m = self.inverse.0;
+        let apply = |col: usize| m[0][col] * n.x + m[1][col] * n.y + m[2][col] * n.z;
+
+        Vector3::new(apply(0), apply(1), apply(2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::sampling;
+
+    fn assert_near(actual: Vector3, expected: Vector3) {
+        assert!(
+            (actual - expected).length() < 1e-9,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    #[test]
+    fn test_transform_composition_and_inverse() {
+        let rotate = Transform::rotation(Vector3::new(0.0, 0.0, 2.0), 90_f64.to_radians()).unwrap();
+        let transform = Transform::scaling(Vector3::new(2.0, 1.0, 1.0))
+            .unwrap()
+            .then(rotate)
+            .then(Transform::translation(Vector3::new(0.0, 0.0, 3.0)));
+
+        // counterclockwise about +z takes +x to +y
+        let p = Vector3::new(1.0, 0.0, 0.0);
+        assert_near(transform.point(p), Vector3::new(0.0, 2.0, 3.0));
+        assert_near(transform.vector(p), Vector3::new(0.0, 2.0, 0.0));
+        assert_near(transform.inverse().point(transform.point(p)), p);
+
+        let general = Transform::from_matrix(transform.matrix()).unwrap();
+        assert_near(general.inverse().point(Vector3::new(0.0, 2.0, 3.0)), p);
+
+        let singular = Matrix4([
+            [1.0, 2.0, 0.0, 0.0],
+            [2.0, 4.0, 0.0, 0.0],
+            [0.0; 4],
+            [0.0; 4],
+        ]);
+        assert!(Transform::from_matrix(singular).is_none());
+    }
+
+    #[test]
+    fn test_transforms_must_be_invertible() {
+        assert!(Transform::scaling(Vector3::new(1.0, 0.0, 2.0)).is_none());
+        assert!(Transform::scaling(Vector3::new(1.0, f64::INFINITY, 2.0)).is_none());
+        assert!(Transform::scaling(Vector3::new(-1.0, 0.5, 2.0)).is_some());
+
+        assert!(Transform::rotation(Vector3::ZERO, 1.0).is_none());
+        assert!(Transform::rotation(Vector3::new(0.0, 1.0, 0.0), f64::NAN).is_none());
+        assert!(Transform::rotation(Vector3::new(0.0, 1e-3, 0.0), 1.0).is_some());
+    }
+
+    #[test]
+    fn test_onb_is_orthonormal() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let normals = [Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0)];
+        let random = (0..100).map(|_| sampling::uniform_sphere(&mut rng) * 3.0);
+
+        for n in normals.into_iter().chain(random) {
+            let onb = ONB::from_w(n);
+            for (a, b) in [(onb.u, onb.v), (onb.v, onb.w), (onb.w, onb.u)] {
+                assert!(dot(a, b).abs() < 1e-12);
+            }
+            for axis in [onb.u, onb.v, onb.w] {
+                assert!((axis.length() - 1.0).abs() < 1e-12);
+            }
+
+            let d = Vector3::new(0.3, -0.2, 0.9);
+            let round_trip = onb.to_local(onb.to_world(d));
+            assert!((round_trip - d).length() < 1e-12);
+        }
+    }
+}
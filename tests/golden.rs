@@ -0,0 +1,187 @@
+//! Renders small, fixed-seed versions of the bundled examples and compares them against the
+//! references in `tests/golden/`.
+//!
+//! After an intentional change to rendering, regenerate the references with
+//! `UPDATE_GOLDEN=1 cargo test --test golden` and check the new images in.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use raytracing::camera::Camera;
+use raytracing::color::{DisplayTransform, ToneMapper, TransferFunction};
+use raytracing::image::{Image, diff, ppm};
+use raytracing::runner::RenderRunner;
+use raytracing::surface::Surface;
+
+#[allow(dead_code)]
+#[path = "../examples/cornell_box.rs"]
+mod cornell_box;
+#[allow(dead_code)]
+#[path = "../examples/demo_spheres.rs"]
+mod demo_spheres;
+#[allow(dead_code)]
+#[path = "../examples/hello_triangle.rs"]
+mod hello_triangle;
+#[allow(dead_code)]
+#[path = "../examples/procedural_textures.rs"]
+mod procedural_textures;
+#[allow(dead_code)]
+#[path = "../examples/quads.rs"]
+mod quads;
+#[allow(dead_code)]
+#[path = "../examples/simple_light.rs"]
+mod simple_light;
+#[allow(dead_code)]
+#[path = "../examples/simple_scene.rs"]
+mod simple_scene;
+
+const IMAGE_WIDTH: u32 = 48;
+const SAMPLES_PER_PIXEL: u32 = 16;
+const SEED: u64 = 0x5eed;
+
+/// Renders are deterministic, so these only leave room for platform differences in
+/// floating point math (e.g. `sin`, `powf`) that occasionally send a path another way. A
+/// single pixel off by one step in every channel stays above them; a systematic bias
+/// doesn't.
+const MIN_PSNR: f64 = 60.0;
+const MIN_SSIM: f64 = 0.999;
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.ppm"))
+}
+
+fn read_ppm(path: &Path) -> Image {
+    let file = File::open(path).unwrap_or_else(|e| {
+        panic!(
+            "couldn't open reference {path:?} ({e}); generate it with UPDATE_GOLDEN=1 cargo test --test golden"
+        )
+    });
+    ppm::read(&mut BufReader::new(file)).unwrap()
+}
+
+fn check(name: &str, camera: Camera, surfaces: Box<[Surface]>) {
+    let camera = Camera {
+        image_width: IMAGE_WIDTH,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        seed: Some(SEED),
+        ..camera
+    };
+
+    let render = RenderRunner {
+        camera,
+        ..Default::default()
+    }
+    .run(surfaces)
+    .unwrap();
+
+    // compare what would actually be displayed, quantization included
+    let mut encoded = Vec::new();
+    ppm::write_binary(&mut encoded, &render.image, &DisplayTransform::default()).unwrap();
+
+    let path = golden_path(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &encoded).unwrap();
+        return;
+    }
+
+    let actual = ppm::read(&mut encoded.as_slice()).unwrap();
+    let expected = read_ppm(&path);
+    assert_eq!(
+        (actual.width(), actual.height()),
+        (expected.width(), expected.height()),
+        "{name}: dimensions differ from the reference"
+    );
+
+    let mse = diff::mse(&actual, &expected);
+    let psnr = diff::psnr(&actual, &expected, 1.0);
+    let ssim = diff::ssim(&actual, &expected);
+
+    if psnr < MIN_PSNR || ssim < MIN_SSIM {
+        let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        fs::create_dir_all(&out_dir).unwrap();
+
+        // the images are already display-encoded, so write their values through unchanged
+        let passthrough = DisplayTransform {
+            tone_mapper: ToneMapper::Clamp,
+            transfer: TransferFunction::Linear,
+        };
+        let mut difference = diff::difference(&actual, &expected);
+        for pixel in difference.pixels_mut() {
+            // small differences are hard to see otherwise
+            *pixel *= 4.0;
+        }
+
+        for (suffix, image) in [
+            ("actual", &actual),
+            ("expected", &expected),
+            ("diff", &difference),
+        ] {
+            image
+                .save(out_dir.join(format!("{name}-{suffix}.png")), &passthrough)
+                .unwrap();
+        }
+
+        panic!(
+            "{name} diverged from its reference: MSE {mse:.6}, PSNR {psnr:.2}dB (min {MIN_PSNR}), SSIM {ssim:.4} (min {MIN_SSIM}). Images written to {out_dir:?}"
+        );
+    }
+}
+
+#[test]
+fn test_cornell_box() {
+    check("cornell_box", cornell_box::camera(), cornell_box::scene());
+}
+
+#[test]
+fn test_demo_spheres() {
+    check(
+        "demo_spheres",
+        demo_spheres::camera(),
+        demo_spheres::demo_spheres().unwrap(),
+    );
+}
+
+#[test]
+fn test_hello_triangle() {
+    check(
+        "hello_triangle",
+        hello_triangle::camera(),
+        hello_triangle::hello_triangle(),
+    );
+}
+
+#[test]
+fn test_procedural_textures() {
+    check(
+        "procedural_textures",
+        procedural_textures::camera(),
+        procedural_textures::procedural_textures().unwrap(),
+    );
+}
+
+#[test]
+fn test_quads() {
+    check("quads", quads::camera(), quads::quads());
+}
+
+#[test]
+fn test_simple_light() {
+    check(
+        "simple_light",
+        simple_light::camera(),
+        simple_light::simple_light().unwrap(),
+    );
+}
+
+#[test]
+fn test_simple_scene() {
+    check(
+        "simple_scene",
+        simple_scene::camera(),
+        simple_scene::simple_scene().unwrap(),
+    );
+}